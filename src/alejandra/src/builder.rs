@@ -1,5 +1,3 @@
-use std::fmt::Display;
-
 #[derive(PartialEq)]
 pub(crate) enum Step {
     Comment(String),
@@ -18,11 +16,79 @@ pub(crate) struct BuildCtx {
     pub force_wide:         bool,
     pub force_wide_success: bool,
     pub indentation:        usize,
+    pub options:            crate::options::FormatOptions,
     pub pos_old:            crate::position::Position,
-    pub path:               String,
+    /// Where the next token goes in the output,
+    /// shared by all the copies of a [`BuildCtx`] but those made to try
+    /// a layout, to know if it fits in the line width.
+    pub pos_new:            std::rc::Rc<std::cell::RefCell<crate::position::Position>>,
+    pub source_map:         Option<std::rc::Rc<std::cell::RefCell<crate::source_map::SourceMap>>>,
+    pub trace:              std::rc::Rc<std::cell::RefCell<Trace>>,
+    /// Nodes to keep as they are in the input,
+    /// found by [`crate::children::Children`] after directive comments.
+    pub verbatim:           std::rc::Rc<std::cell::RefCell<std::collections::HashSet<rnix::SyntaxNode>>>,
     pub vertical:           bool,
 }
 
+impl BuildCtx {
+    /// A copy of the position in the output,
+    /// for a [`BuildCtx`] that tries a layout without emitting it.
+    pub(crate) fn trial_pos_new(&self) -> std::rc::Rc<std::cell::RefCell<crate::position::Position>> {
+        std::rc::Rc::new(std::cell::RefCell::new(self.pos_new.borrow().clone()))
+    }
+}
+
+/// What is being formatted, shared by all the copies of a [`BuildCtx`],
+/// to explain internal errors.
+#[derive(Default)]
+pub(crate) struct Trace {
+    /// Kind and start of the nodes being formatted,
+    /// from the outermost to the innermost.
+    pub nodes:        Vec<(rnix::SyntaxKind, rnix::TextSize)>,
+    /// The first node found that no rule knows how to format.
+    pub missing_rule: Option<(rnix::SyntaxKind, rnix::TextSize)>,
+}
+
+/// Runs `build` with `build_ctx`, a function that calls [`build`],
+/// turning panics and unknown nodes of `source` into an internal error.
+pub(crate) fn try_build<F>(
+    build_ctx: &mut BuildCtx,
+    source: &str,
+    build: F,
+) -> Result<rowan::GreenNode, crate::error::InternalError>
+where
+    F: FnOnce(&mut BuildCtx) -> Option<rowan::GreenNode>,
+{
+    let location = |offset: rnix::TextSize| crate::error::Location::from_offset(source, offset.into());
+
+    let result = crate::error::InternalError::catch(|| build(build_ctx));
+    let trace = build_ctx.trace.borrow();
+
+    if let Some((kind, offset)) = trace.missing_rule {
+        return Err(crate::error::InternalError {
+            message:  "missing rule".to_string(),
+            kind:     Some(kind),
+            location: Some(location(offset)),
+        });
+    }
+
+    match result {
+        Ok(Some(green_node)) => Ok(green_node),
+        Ok(None) => Err(crate::error::InternalError {
+            message:  "unable to build the syntax tree".to_string(),
+            kind:     None,
+            location: None,
+        }),
+        Err(mut error) => {
+            if let Some(&(kind, offset)) = trace.nodes.last() {
+                error.kind = Some(kind);
+                error.location = Some(location(offset));
+            }
+            Err(error)
+        }
+    }
+}
+
 pub(crate) fn build(build_ctx: &mut BuildCtx, element: rnix::SyntaxElement) -> Option<rowan::GreenNode> {
     let mut builder = rowan::GreenNodeBuilder::new();
 
@@ -60,12 +126,17 @@ fn build_step(builder: &mut rowan::GreenNodeBuilder, build_ctx: &mut BuildCtx, s
                     if index == 0 || line.is_empty() {
                         line.to_string()
                     } else {
-                        format!("{0:<1$}{2}", "", 2 * build_ctx.indentation, line,)
+                        format!("{}{}", build_ctx.options.indent(build_ctx.indentation), line)
                     }
                 })
                 .collect();
 
-            add_token(builder, rnix::SyntaxKind::TOKEN_COMMENT, &lines.join("\n"));
+            add_token(
+                builder,
+                build_ctx,
+                rnix::SyntaxKind::TOKEN_COMMENT,
+                &lines.join("\n"),
+            );
         }
         Dedent => {
             build_ctx.indentation -= 1;
@@ -82,34 +153,86 @@ fn build_step(builder: &mut rowan::GreenNodeBuilder, build_ctx: &mut BuildCtx, s
         NewLine => {
             build_ctx.force_wide_success = false;
 
-            add_token(builder, rnix::SyntaxKind::TOKEN_WHITESPACE, "\n");
+            add_token(builder, build_ctx, rnix::SyntaxKind::TOKEN_WHITESPACE, "\n");
         }
         Pad => {
             if build_ctx.indentation > 0 {
                 add_token(
                     builder,
+                    build_ctx,
                     rnix::SyntaxKind::TOKEN_WHITESPACE,
-                    &format!("{0:<1$}", "", 2 * build_ctx.indentation),
+                    &build_ctx.options.indent(build_ctx.indentation),
                 );
             }
         }
         Token(kind, text) => {
-            add_token(builder, *kind, text);
+            add_token(builder, build_ctx, *kind, text);
         }
         Whitespace => {
-            add_token(builder, rnix::SyntaxKind::TOKEN_WHITESPACE, " ");
+            add_token(builder, build_ctx, rnix::SyntaxKind::TOKEN_WHITESPACE, " ");
         }
     }
 }
 
-fn add_token(builder: &mut rowan::GreenNodeBuilder, kind: rnix::SyntaxKind, text: &str) {
+fn add_token(
+    builder: &mut rowan::GreenNodeBuilder,
+    build_ctx: &mut BuildCtx,
+    kind: rnix::SyntaxKind,
+    text: &str,
+) {
     builder.token(rowan::SyntaxKind(kind as u16), text);
+    advance(build_ctx, text);
+
+    if let Some(source_map) = &build_ctx.source_map {
+        source_map.borrow_mut().emit(None, text);
+    }
+}
+
+/// Moves the position in the output past `text`,
+/// and when forcing a wide layout, gives up if it goes past the line width.
+fn advance(build_ctx: &mut BuildCtx, text: &str) {
+    let mut pos_new = build_ctx.pos_new.borrow_mut();
+    pos_new.update(text);
+
+    if build_ctx.options.indent_style == crate::options::IndentStyle::Tabs {
+        let last_line = text.rsplit('\n').next().unwrap_or_default();
+        pos_new.column += last_line.matches('\t').count() * build_ctx.options.indent_width.saturating_sub(1);
+    }
+
+    if build_ctx.force_wide && pos_new.column > build_ctx.options.line_width {
+        build_ctx.force_wide_success = false;
+    }
 }
 
 fn format(builder: &mut rowan::GreenNodeBuilder, build_ctx: &mut BuildCtx, element: &rnix::SyntaxElement) {
     let kind = element.kind();
 
     match element {
+        rnix::SyntaxElement::Node(node) if build_ctx.verbatim.borrow().contains(node) => {
+            let run = crate::children::verbatim_run(&build_ctx.verbatim.borrow(), node);
+
+            builder.start_node(rowan::SyntaxKind(kind as u16));
+            let mut text = String::new();
+            for element in &run {
+                let tokens: Vec<rnix::SyntaxToken> = match element {
+                    rnix::SyntaxElement::Node(node) => node
+                        .descendants_with_tokens()
+                        .filter_map(|child| child.into_token())
+                        .collect(),
+                    rnix::SyntaxElement::Token(token) => vec![token.clone()],
+                };
+                for token in tokens {
+                    text.push_str(token.text());
+                    format(builder, build_ctx, &token.into());
+                }
+            }
+            builder.finish_node();
+
+            // It takes the lines that it takes.
+            if crate::utils::has_newlines(&text) {
+                build_ctx.force_wide_success = false;
+            }
+        }
         rnix::SyntaxElement::Node(node) => {
             builder.start_node(rowan::SyntaxKind(kind as u16));
 
@@ -191,20 +314,54 @@ fn format(builder: &mut rowan::GreenNodeBuilder, build_ctx: &mut BuildCtx, eleme
                 // with a; b
                 NODE_WITH => crate::rules::scoped::rule,
                 kind => {
-                    panic!("Missing rule for {:?} at: {}", kind, build_ctx.path);
+                    build_ctx
+                        .trace
+                        .borrow_mut()
+                        .missing_rule
+                        .get_or_insert((kind, node.text_range().start()));
+                    builder.finish_node();
+                    return;
                 }
             };
 
+            // Strings keep their lines, whatever their width,
+            // so their interpolations are not split to make them fit,
+            // and are indented with spaces, the only indentation Nix strips.
+            let options = build_ctx.options;
+            if kind == NODE_STRING {
+                build_ctx.options.line_width = usize::MAX;
+                build_ctx.options.indent_style = crate::options::IndentStyle::Spaces;
+            }
+
+            build_ctx
+                .trace
+                .borrow_mut()
+                .nodes
+                .push((kind, node.text_range().start()));
             for step in rule(build_ctx, node) {
                 build_step(builder, build_ctx, &step);
             }
+            build_ctx.trace.borrow_mut().nodes.pop();
+
+            build_ctx.options = options;
 
             builder.finish_node();
         }
         rnix::SyntaxElement::Token(token) => {
             let text = token.text();
-            add_token(builder, kind, text);
+            builder.token(rowan::SyntaxKind(kind as u16), text);
             build_ctx.pos_old.update(text);
+            advance(build_ctx, text);
+
+            if let Some(source_map) = &build_ctx.source_map {
+                // Tokens right under the root are trivia,
+                // or were made up by the rules with `make_isolated_token`
+                let old = match token.parent().kind() {
+                    rnix::SyntaxKind::NODE_ROOT => None,
+                    _ => Some(token.text_range().start().into()),
+                };
+                source_map.borrow_mut().emit(old, text);
+            }
         }
     }
 }
@@ -217,6 +374,7 @@ fn format_wider(builder: &mut rowan::GreenNodeBuilder, build_ctx: &mut BuildCtx,
             build_ctx_clone.vertical = !fits_in_single_line(build_ctx, node.clone().into());
 
             format(builder, &mut build_ctx_clone, element);
+            build_ctx.force_wide_success &= build_ctx_clone.force_wide_success;
         }
         rnix::SyntaxElement::Token(_) => {
             format(builder, build_ctx, element);
@@ -224,11 +382,64 @@ fn format_wider(builder: &mut rowan::GreenNodeBuilder, build_ctx: &mut BuildCtx,
     };
 }
 
+/// A copy of `build_ctx` to try layouts with, placed where `steps` would
+/// leave it if emitted from the current position, to know if what follows
+/// them fits in the line width.
+pub(crate) fn trial_after(build_ctx: &BuildCtx, steps: &[Step]) -> BuildCtx {
+    let mut build_ctx = BuildCtx {
+        force_wide: false,
+        pos_new: build_ctx.trial_pos_new(),
+        source_map: None,
+        ..build_ctx.clone()
+    };
+
+    let mut builder = rowan::GreenNodeBuilder::new();
+    for step in steps {
+        build_step(&mut builder, &mut build_ctx, step);
+    }
+
+    build_ctx
+}
+
+/// Whether every line that `steps` emit from the current position
+/// stays within the line width.
+pub(crate) fn fits_in_width(build_ctx: &BuildCtx, steps: &[Step]) -> bool {
+    let mut build_ctx = BuildCtx {
+        force_wide: false,
+        pos_new: build_ctx.trial_pos_new(),
+        source_map: None,
+        ..build_ctx.clone()
+    };
+    let column = build_ctx.pos_new.borrow().column;
+
+    let mut builder = rowan::GreenNodeBuilder::new();
+    builder.start_node(rowan::SyntaxKind(rnix::SyntaxKind::NODE_ROOT as u16));
+    for step in steps {
+        build_step(&mut builder, &mut build_ctx, step);
+    }
+    builder.finish_node();
+
+    let text = rnix::SyntaxNode::new_root(builder.finish())
+        .text()
+        .to_string();
+    text.split('\n').enumerate().all(|(index, line)| {
+        let mut width = if index == 0 { column } else { 0 } + line.chars().count();
+        if build_ctx.options.indent_style == crate::options::IndentStyle::Tabs {
+            width += line.matches('\t').count() * build_ctx.options.indent_width.saturating_sub(1);
+        }
+        width <= build_ctx.options.line_width
+    })
+}
+
+/// Whether `element` can be formatted without new lines,
+/// and without going past the line width from the current position.
 pub(crate) fn fits_in_single_line(build_ctx_old: &BuildCtx, element: rnix::SyntaxElement) -> bool {
     // eprintln!("Checking if {} fits on a single line", element);
     let mut build_ctx = BuildCtx {
         force_wide: true,
         force_wide_success: true,
+        pos_new: build_ctx_old.trial_pos_new(),
+        source_map: None,
         vertical: false,
         ..build_ctx_old.clone()
     };
@@ -28,10 +28,39 @@ impl Children {
             }
         };
 
+        // Whether the following nodes are kept as they are,
+        // see [`Directive`].
+        let mut off = false;
+        let mut skip = false;
+        // Whether the last child is a node kept as it is,
+        // maybe followed by whitespace.
+        let mut after_verbatim = false;
+
         for child in node.children_with_tokens() {
             match child {
                 rnix::SyntaxElement::Node(node) => {
                     match node.kind() {
+                        _ if off || skip => {
+                            build_ctx.verbatim.borrow_mut().insert(node.clone());
+                            if after_verbatim && joins_verbatim(&node) {
+                                // It goes with the previous node,
+                                // along with the whitespace between them.
+                                while children
+                                    .last()
+                                    .is_some_and(|child| child.kind() == rnix::SyntaxKind::TOKEN_WHITESPACE)
+                                {
+                                    children.pop();
+                                }
+                            } else {
+                                children.push(node.clone().into());
+                            }
+                            after_verbatim = true;
+                            skip = false;
+                            if let Some(pos) = pos.as_mut() {
+                                pos.update(&node.text().to_string());
+                            }
+                            continue;
+                        }
                         rnix::SyntaxKind::NODE_PAREN => {
                             let mut simplified = node.clone();
 
@@ -71,15 +100,27 @@ impl Children {
                             children.push(node.clone().into());
                         }
                     }
+                    after_verbatim = false;
 
-                    if pos.is_some() {
-                        pos.as_mut().unwrap().update(&node.text().to_string());
+                    if let Some(pos) = pos.as_mut() {
+                        pos.update(&node.text().to_string());
                     }
                 }
 
                 rnix::SyntaxElement::Token(token) => {
+                    if token.kind() != rnix::SyntaxKind::TOKEN_WHITESPACE {
+                        after_verbatim = false;
+                    }
+
                     match token.kind() {
                         rnix::SyntaxKind::TOKEN_COMMENT => {
+                            match Directive::parse(token.text()) {
+                                Some(Directive::Off) => off = true,
+                                Some(Directive::On) => off = false,
+                                Some(Directive::Skip) => skip = true,
+                                None => {}
+                            }
+
                             children.push(
                                 crate::builder::make_isolated_token(
                                     rnix::SyntaxKind::TOKEN_COMMENT,
@@ -98,8 +139,8 @@ impl Children {
                         }
                     }
 
-                    if pos.is_some() {
-                        pos.as_mut().unwrap().update(token.text());
+                    if let Some(pos) = pos.as_mut() {
+                        pos.update(token.text());
                     }
                 }
             }
@@ -181,6 +222,69 @@ impl Children {
     }
 }
 
+/// A comment that tells Alejandra to keep code as it is:
+/// `# alejandra: off` until `# alejandra: on`
+/// for the nodes in between that have the same parent,
+/// and `# alejandra: skip` for the node after it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Directive {
+    Off,
+    On,
+    Skip,
+}
+
+impl Directive {
+    fn parse(comment: &str) -> Option<Directive> {
+        match comment.strip_prefix('#')?.trim() {
+            "alejandra: off" => Some(Directive::Off),
+            "alejandra: on" => Some(Directive::On),
+            "alejandra: skip" => Some(Directive::Skip),
+            _ => None,
+        }
+    }
+}
+
+/// Whether `node` is an item of a list, attribute set or `let`,
+/// which are kept as they are together with the whitespace between them
+/// when they follow each other in a region that is off.
+fn joins_verbatim(node: &rnix::SyntaxNode) -> bool {
+    node.parent().is_some_and(|parent| {
+        matches!(
+            parent.kind(),
+            rnix::SyntaxKind::NODE_ATTR_SET | rnix::SyntaxKind::NODE_LET_IN | rnix::SyntaxKind::NODE_LIST
+        )
+    })
+}
+
+/// The elements kept as they are from `node` on: `node`, and the nodes
+/// that go with it as [`Children::new`] found them, with the whitespace
+/// between them.
+pub(crate) fn verbatim_run(
+    verbatim: &std::collections::HashSet<rnix::SyntaxNode>,
+    node: &rnix::SyntaxNode,
+) -> Vec<rnix::SyntaxElement> {
+    let mut run = vec![rnix::SyntaxElement::Node(node.clone())];
+    if !joins_verbatim(node) {
+        return run;
+    }
+
+    let mut whitespace = Vec::new();
+    for sibling in node.siblings_with_tokens(rowan::Direction::Next).skip(1) {
+        match sibling {
+            rnix::SyntaxElement::Token(token) if token.kind() == rnix::SyntaxKind::TOKEN_WHITESPACE => {
+                whitespace.push(token.into());
+            }
+            rnix::SyntaxElement::Node(sibling) if verbatim.contains(&sibling) => {
+                run.append(&mut whitespace);
+                run.push(sibling.into());
+            }
+            _ => break,
+        }
+    }
+
+    run
+}
+
 fn dedent_comment(pos: &crate::position::Position, text: &str) -> String {
     if text.starts_with('#') {
         text.to_string()
@@ -2,6 +2,7 @@ use std::collections::LinkedList;
 
 pub(crate) enum Trivia {
     Comment(String),
+    #[allow(dead_code)]
     Newlines(usize),
 }
 
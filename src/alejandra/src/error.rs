@@ -0,0 +1,198 @@
+/// A line and column in the source code, both starting at 1.
+///
+/// Columns are counted in characters, not bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Location {
+    /// Line number, starting at 1.
+    pub line:   usize,
+    /// Column number, starting at 1.
+    pub column: usize,
+}
+
+impl Location {
+    /// Translates a byte `offset` into `source` to a line and column.
+    pub(crate) fn from_offset(source: &str, offset: usize) -> Location {
+        let mut position = crate::position::Position::default();
+        position.update(&source[..offset]);
+
+        Location {
+            line:   position.line,
+            column: position.column + 1,
+        }
+    }
+}
+
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// A syntax error found while parsing the input.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    /// What went wrong.
+    pub message: String,
+    /// The range of the input where the error was found.
+    pub range:   rnix::TextRange,
+    /// Where `range` starts.
+    pub start:   Location,
+    /// Where `range` ends.
+    pub end:     Location,
+}
+
+impl ParseError {
+    pub(crate) fn new(source: &str, error: &rnix::parser::ParseError) -> ParseError {
+        use rnix::parser::ParseError::*;
+
+        // Errors at the end of file do not carry a range,
+        // so point at the end of the input instead.
+        let end_of_file = rnix::TextRange::empty(rnix::TextSize::of(source));
+
+        let (message, range) = match error {
+            Unexpected(range) => ("unexpected syntax".to_string(), *range),
+            UnexpectedExtra(range) => ("unexpected token".to_string(), *range),
+            UnexpectedWanted(got, range, wanted) => {
+                (format!("unexpected {got:?}, wanted any of {wanted:?}"), *range)
+            }
+            UnexpectedDoubleBind(range) => ("unexpected double bind".to_string(), *range),
+            UnexpectedEOF => ("unexpected end of file".to_string(), end_of_file),
+            UnexpectedEOFWanted(wanted) => (
+                format!("unexpected end of file, wanted any of {wanted:?}"),
+                end_of_file,
+            ),
+            DuplicatedArgs(range, ident) => (format!("argument `{ident}` is duplicated"), *range),
+            error => (error.to_string(), end_of_file),
+        };
+
+        ParseError {
+            message,
+            range,
+            start: Location::from_offset(source, range.start().into()),
+            end: Location::from_offset(source, range.end().into()),
+        }
+    }
+
+    /// Translates the range of the error with `to_input`,
+    /// when the parsed source was a normalized copy of the input,
+    /// whose lines and columns are the same.
+    pub(crate) fn in_input<F>(mut self, to_input: F) -> ParseError
+    where
+        F: Fn(usize) -> usize,
+    {
+        let start = to_input(self.range.start().into());
+        let end = to_input(self.range.end().into());
+        self.range = rnix::TextRange::new((start as u32).into(), (end as u32).into());
+
+        self
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at {}", self.message, self.start)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// A failure of Alejandra to format valid input. This is a bug.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InternalError {
+    /// What went wrong.
+    pub message:  String,
+    /// The kind of the innermost node being formatted, if known.
+    pub kind:     Option<rnix::SyntaxKind>,
+    /// Where that node starts, if known.
+    pub location: Option<Location>,
+}
+
+impl InternalError {
+    /// Runs `f`, and turns a panic into an internal error.
+    pub(crate) fn catch<T>(f: impl FnOnce() -> T) -> Result<T, InternalError> {
+        std::panic::catch_unwind(std::panic::AssertUnwindSafe(f))
+            .map_err(|payload| InternalError::from_panic(payload.as_ref()))
+    }
+
+    /// Describes a panic from its payload, as returned by
+    /// [`std::panic::catch_unwind`].
+    pub fn from_panic(payload: &(dyn std::any::Any + Send)) -> InternalError {
+        let message = match payload.downcast_ref::<&str>() {
+            Some(message) => message.to_string(),
+            None => match payload.downcast_ref::<String>() {
+                Some(message) => message.clone(),
+                None => "unknown panic".to_string(),
+            },
+        };
+
+        InternalError {
+            message,
+            kind: None,
+            location: None,
+        }
+    }
+}
+
+impl std::fmt::Display for InternalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)?;
+        if let Some(kind) = self.kind {
+            write!(f, " while formatting {kind:?}")?;
+        }
+        if let Some(location) = self.location {
+            write!(f, " at {location}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for InternalError {}
+
+/// Possible errors while formatting.
+#[derive(Clone, Debug)]
+pub enum Error {
+    /// The input is not valid Nix code,
+    /// and every syntax error found, in order of appearance.
+    Parse(Vec<ParseError>),
+    /// The input could not be read, or the output could not be written.
+    Io(std::sync::Arc<std::io::Error>),
+    /// The output of Alejandra does not mean the same as its input,
+    /// or formatting it again changes it,
+    /// and the first difference found. This is a bug.
+    Verification(String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Parse(errors) => match &errors[..] {
+                [] => write!(f, "syntax error"),
+                [error] => write!(f, "{error}"),
+                [error, rest @ ..] => write!(
+                    f,
+                    "{error} (and {} more syntax error{})",
+                    rest.len(),
+                    if rest.len() == 1 { "" } else { "s" }
+                ),
+            },
+            Error::Io(error) => write!(f, "{error}"),
+            Error::Verification(reason) => write!(f, "verification failed: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Parse(errors) => errors.first().map(|error| error as _),
+            Error::Io(error) => Some(error.as_ref()),
+            Error::Verification(_) => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Error {
+        Error::Io(std::sync::Arc::new(error))
+    }
+}
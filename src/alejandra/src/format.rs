@@ -1,8 +1,16 @@
+use crate::normalize::Normalized;
+use crate::options::FormatOptions;
+use crate::options::LineEnding;
+use crate::source_map::SourceMap;
+
 /// Possibles results after formatting.
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub enum Status {
     /// An error ocurred, and its reason.
-    Error(String),
+    Error(crate::error::Error),
+    /// Alejandra failed to format valid input, which is a bug,
+    /// so the input was returned unchanged.
+    InternalError(crate::error::InternalError),
     /// Formatting was successful,
     /// the file changed or not according to the boolean.
     Changed(bool),
@@ -10,59 +18,298 @@ pub enum Status {
 
 impl From<std::io::Error> for Status {
     fn from(error: std::io::Error) -> Status {
-        Status::Error(error.to_string())
+        Status::Error(error.into())
     }
 }
 
+/// Formats the content of `before` in-memory.
+///
+/// `path` is not used, and only kept for compatibility.
+pub fn in_memory(_path: String, before: String) -> (Status, String) {
+    in_memory_with(before, &FormatOptions::default())
+}
+
+/// Formats the content of `before` in-memory like [`in_memory`],
+/// according to `options`.
+pub fn in_memory_with(before: String, options: &FormatOptions) -> (Status, String) {
+    let (status, after, _) = in_memory_with_source_map(before, options);
+
+    (status, after)
+}
+
 /// Formats the content of `before` in-memory,
-/// and assume `path` in the displayed error messages
-pub fn in_memory(path: String, before: String) -> (Status, String) {
-    let tokens = rnix::tokenizer::Tokenizer::new(&before);
+/// according to `options`.
+///
+/// Also returns a map from offsets in `before` to offsets in the result.
+pub fn in_memory_with_source_map(before: String, options: &FormatOptions) -> (Status, String, SourceMap) {
+    let normalized = Normalized::new(&before, options.line_ending);
+
+    let tokens = rnix::tokenizer::Tokenizer::new(&normalized.text);
     let ast = rnix::parser::parse(tokens);
 
-    let errors = ast.errors();
+    let errors = parse_errors(&normalized, &ast);
     if !errors.is_empty() {
-        return (Status::Error(errors[0].to_string()), before);
+        let source_map = SourceMap::identity(before.len());
+        return (
+            Status::Error(crate::error::Error::Parse(errors)),
+            before,
+            source_map,
+        );
     }
 
+    let source_map = std::rc::Rc::new(std::cell::RefCell::new(SourceMap::new(normalized.text.len())));
+
     let mut build_ctx = crate::builder::BuildCtx {
-        force_wide: false,
+        force_wide:         false,
         force_wide_success: true,
-        indentation: 0,
-        path,
-        pos_old: crate::position::Position::default(),
-        vertical: true,
+        indentation:        0,
+        options:            *options,
+        pos_old:            crate::position::Position::default(),
+        pos_new:            Default::default(),
+        source_map:         Some(source_map.clone()),
+        trace:              Default::default(),
+        verbatim:           Default::default(),
+        vertical:           true,
+    };
+
+    let after = match crate::builder::try_build(&mut build_ctx, &normalized.text, |build_ctx| {
+        crate::builder::build(build_ctx, ast.node().into())
+    }) {
+        Ok(after) => after.to_string(),
+        Err(error) => {
+            let source_map = SourceMap::identity(before.len());
+            return (Status::InternalError(error), before, source_map);
+        }
+    };
+
+    let new_crlf: Vec<usize> = match normalized.line_ending {
+        LineEnding::Crlf => after.match_indices('\n').map(|(index, _)| index).collect(),
+        _ => Vec::new(),
+    };
+    let source_map = source_map
+        .take()
+        .denormalize(normalized.prefix_len(), &normalized.crlf, &new_crlf)
+        .finish();
+    let after = normalized.restore(&after);
+
+    if before == after {
+        (Status::Changed(false), after, source_map)
+    } else {
+        (Status::Changed(true), after, source_map)
+    }
+}
+
+/// Checks that `before` is valid Nix, without formatting it.
+pub fn check_syntax(before: &str) -> Result<(), crate::error::Error> {
+    let normalized = Normalized::new(before, LineEnding::Auto);
+
+    let tokens = rnix::tokenizer::Tokenizer::new(&normalized.text);
+    let ast = rnix::parser::parse(tokens);
+
+    let errors = parse_errors(&normalized, &ast);
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(crate::error::Error::Parse(errors))
+    }
+}
+
+/// The syntax errors of `ast`, parsed from `normalized`,
+/// at their position in the input and sorted by it.
+fn parse_errors(normalized: &Normalized, ast: &rnix::AST) -> Vec<crate::error::ParseError> {
+    let mut errors: Vec<_> = ast
+        .errors()
+        .iter()
+        .map(|error| {
+            crate::error::ParseError::new(&normalized.text, error)
+                .in_input(|offset| normalized.text_to_input(offset))
+        })
+        .collect();
+    errors.sort_by_key(|error| (error.range.start(), error.range.end()));
+    errors.dedup();
+
+    errors
+}
+
+/// Checks that `after` means the same as `before`,
+/// that is, that both parse to the same syntax tree
+/// after ignoring whitespace, comments and redundant parentheses.
+pub fn check_equivalent(before: &str, after: &str) -> Result<(), crate::error::Error> {
+    crate::verify::equivalent(before, after).map_err(crate::error::Error::Verification)
+}
+
+/// Formats `before` twice according to `options`, and makes sure that the
+/// second pass changes nothing, that is, that formatting is idempotent.
+///
+/// Returns the result of the first pass like [`in_memory_with`],
+/// or an error and `before` unchanged if the second pass changes it.
+pub fn check_idempotent(before: String, options: &FormatOptions) -> (Status, String) {
+    let (status, first) = in_memory_with(before.clone(), options);
+    if !matches!(status, Status::Changed(_)) {
+        return (status, first);
+    }
+
+    let (second_status, second) = in_memory_with(first.clone(), options);
+    let difference = match second_status {
+        Status::Error(error) => format!("the first pass does not parse: {error}"),
+        Status::InternalError(error) => format!("the first pass cannot be formatted: {error}"),
+        Status::Changed(_) if first == second => return (status, first),
+        Status::Changed(_) => crate::verify::first_difference(
+            &crate::verify::normalized(&first),
+            &crate::verify::normalized(&second),
+        )
+        .unwrap_or_else(|| "the second pass changes the line endings".to_string()),
     };
 
-    let after = crate::builder::build(&mut build_ctx, ast.node().into())
-        .unwrap()
-        .to_string();
+    (
+        Status::Error(crate::error::Error::Verification(difference)),
+        before,
+    )
+}
+
+/// Formats only the smallest nodes of `before` that enclose `range`,
+/// according to `options`.
+///
+/// `path` is not used, and only kept for symmetry with [`in_memory`].
+///
+/// Returns the whole new text,
+/// and the range of it that was re-formatted,
+/// which is empty if `range` does not touch any code.
+pub fn in_memory_range(
+    _path: String,
+    before: String,
+    range: rnix::TextRange,
+    options: &FormatOptions,
+) -> (Status, String, rnix::TextRange) {
+    let normalized = Normalized::new(&before, options.line_ending);
+    let source = &normalized.text;
+
+    let tokens = rnix::tokenizer::Tokenizer::new(source);
+    let ast = rnix::parser::parse(tokens);
+
+    if !ast.errors().is_empty() {
+        let (status, after) = in_memory_with(before, options);
+        return (status, after, range);
+    }
+
+    let root = ast.node();
+    let normalized_range = rnix::TextRange::new(
+        (normalized.input_to_text(range.start().into()) as u32).into(),
+        (normalized.input_to_text(range.end().into()) as u32).into(),
+    );
+    let nodes = crate::range::enclosing_nodes(&root, normalized_range);
+
+    let (first, last) = match (nodes.first(), nodes.last()) {
+        (Some(first), Some(last)) => (first.text_range(), last.text_range()),
+        _ => {
+            let edited = rnix::TextRange::empty(range.start().min(rnix::TextSize::of(&before)));
+            return (Status::Changed(false), before, edited);
+        }
+    };
+
+    let mut formatted = String::new();
+    let mut previous_end = first.start();
+    for node in nodes {
+        let node_range = node.text_range();
+        formatted.push_str(&source[rnix::TextRange::new(previous_end, node_range.start())]);
+        previous_end = node_range.end();
+
+        let mut pos_old = crate::position::Position::default();
+        pos_old.update(&source[..usize::from(node_range.start())]);
+
+        let mut build_ctx = crate::builder::BuildCtx {
+            force_wide: false,
+            force_wide_success: true,
+            indentation: crate::range::indentation_at(
+                source,
+                node_range.start().into(),
+                options.indent_width,
+            ),
+            options: *options,
+            pos_new: std::rc::Rc::new(std::cell::RefCell::new(pos_old.clone())),
+            pos_old,
+            source_map: None,
+            trace: Default::default(),
+            verbatim: Default::default(),
+            vertical: true,
+        };
+
+        // Directive comments in the ancestors can keep the node as it is.
+        for ancestor in node.ancestors().skip(1) {
+            crate::children::Children::new(&build_ctx, &ancestor);
+        }
+        if node
+            .ancestors()
+            .any(|ancestor| build_ctx.verbatim.borrow().contains(&ancestor))
+        {
+            formatted.push_str(&node.to_string());
+            continue;
+        }
+
+        match crate::builder::try_build(&mut build_ctx, source, |build_ctx| {
+            build_ctx.vertical = !crate::builder::fits_in_single_line(build_ctx, node.clone().into());
+            crate::builder::build(build_ctx, node.into())
+        }) {
+            Ok(node) => formatted.push_str(&node.to_string()),
+            Err(error) => return (Status::InternalError(error), before, range),
+        }
+    }
+
+    // Only the formatted nodes change, the rest of the input is kept as is.
+    let first_start = normalized.text_to_input(first.start().into());
+    let last_end = normalized.text_to_input(last.end().into());
+    let formatted = normalized.restore_line_endings(&formatted);
+
+    let edited = rnix::TextRange::at((first_start as u32).into(), rnix::TextSize::of(&formatted));
+    let after = format!("{}{}{}", &before[..first_start], formatted, &before[last_end..]);
 
     if before == after {
-        (Status::Changed(false), after)
+        (Status::Changed(false), after, edited)
     } else {
-        (Status::Changed(true), after)
+        (Status::Changed(true), after, edited)
     }
 }
 
 /// Formats the file at `path`,
 /// optionally overriding it's contents if `in_place` is true.
 pub fn in_fs(path: String, in_place: bool) -> Status {
-    use std::io::Write;
+    in_fs_using(std::path::Path::new(&path), in_place, None, in_memory)
+}
+
+/// Formats the file at `path` like [`in_fs`],
+/// according to `options`.
+pub fn in_fs_with(path: String, in_place: bool, options: &FormatOptions) -> Status {
+    in_fs_using(std::path::Path::new(&path), in_place, None, |_, before| {
+        in_memory_with(before, options)
+    })
+}
 
-    match std::fs::read_to_string(&path) {
+/// Formats the file at `path` with `formatter`,
+/// a function like [`in_memory`],
+/// optionally overriding it's contents if `in_place` is true,
+/// in which case the original is kept in a file with `backup_suffix`
+/// appended to its name, if given.
+///
+/// See [`crate::write::atomically`] for how files are written.
+pub fn in_fs_using<F>(
+    path: &std::path::Path,
+    in_place: bool,
+    backup_suffix: Option<&str>,
+    formatter: F,
+) -> Status
+where
+    F: FnOnce(String, String) -> (Status, String),
+{
+    match std::fs::read_to_string(path) {
         Ok(before) => {
-            let (status, data) = crate::format::in_memory(path.clone(), before);
+            let (status, data) = formatter(path.to_string_lossy().into_owned(), before);
 
             match status {
                 Status::Changed(changed) => {
                     if in_place {
                         if changed {
-                            match std::fs::File::create(path) {
-                                Ok(mut file) => match file.write_all(data.as_bytes()) {
-                                    Ok(_) => Status::Changed(true),
-                                    Err(error) => Status::from(error),
-                                },
+                            match crate::write::atomically(path, &data, backup_suffix) {
+                                Ok(()) => Status::Changed(true),
                                 Err(error) => Status::from(error),
                             }
                         } else {
@@ -72,7 +319,7 @@ pub fn in_fs(path: String, in_place: bool) -> Status {
                         Status::Changed(changed)
                     }
                 }
-                Status::Error(error) => Status::Error(error),
+                status => status,
             }
         }
         Err(error) => Status::from(error),
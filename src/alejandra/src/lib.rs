@@ -44,11 +44,22 @@ static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
 pub(crate) mod builder;
 pub(crate) mod children;
 pub(crate) mod children2;
+/// Errors that can occur while formatting.
+pub mod error;
 /// Functions for formatting Nix code.
 pub mod format;
+pub(crate) mod normalize;
+/// Options to customize the formatting.
+pub mod options;
 pub(crate) mod parsers;
 pub(crate) mod position;
+pub(crate) mod range;
 pub(crate) mod rules;
+/// Mapping from offsets in the input to offsets in the output.
+pub mod source_map;
 pub(crate) mod utils;
+pub(crate) mod verify;
 /// Metadata.
 pub mod version;
+/// Writing files in-place safely.
+pub mod write;
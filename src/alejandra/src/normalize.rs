@@ -0,0 +1,97 @@
+use crate::options::LineEnding;
+
+/// The byte order mark that some editors put at the start of UTF-8 files.
+const BOM: &str = "\u{feff}";
+
+/// A copy of the input without its byte order mark,
+/// and with its `\r\n` line endings turned into `\n`,
+/// which is all that the rules know how to handle.
+pub(crate) struct Normalized {
+    pub text:        String,
+    /// Whether the input started with a byte order mark.
+    pub bom:         bool,
+    /// Offsets in `text` of the `\n` that were `\r\n` in the input.
+    pub crlf:        Vec<usize>,
+    /// How lines end in the output,
+    /// as requested or else as most lines of the input end.
+    pub line_ending: LineEnding,
+}
+
+impl Normalized {
+    pub(crate) fn new(input: &str, line_ending: LineEnding) -> Normalized {
+        let bom = input.starts_with(BOM);
+        let input = input.strip_prefix(BOM).unwrap_or(input);
+
+        let mut text = String::with_capacity(input.len());
+        let mut crlf = Vec::new();
+        for line in input.split_inclusive('\n') {
+            match line.strip_suffix("\r\n") {
+                Some(line) => {
+                    text.push_str(line);
+                    crlf.push(text.len());
+                    text.push('\n');
+                }
+                None => text.push_str(line),
+            }
+        }
+
+        let line_ending = match line_ending {
+            LineEnding::Auto => {
+                let lf = text.matches('\n').count() - crlf.len();
+                if crlf.len() > lf {
+                    LineEnding::Crlf
+                } else {
+                    LineEnding::Lf
+                }
+            }
+            line_ending => line_ending,
+        };
+
+        Normalized {
+            text,
+            bom,
+            crlf,
+            line_ending,
+        }
+    }
+
+    /// Number of bytes before the text in the input and in the output.
+    pub(crate) fn prefix_len(&self) -> usize {
+        if self.bom { BOM.len() } else { 0 }
+    }
+
+    /// Translates `offset` in the input to the same spot in `text`.
+    pub(crate) fn input_to_text(&self, offset: usize) -> usize {
+        let mut offset = offset.saturating_sub(self.prefix_len());
+        // Each `\r` before `offset` moves it one byte back.
+        for newline in &self.crlf {
+            if *newline >= offset {
+                break;
+            }
+            offset -= 1;
+        }
+        offset
+    }
+
+    /// Translates `offset` in `text` to the same spot in the input.
+    pub(crate) fn text_to_input(&self, offset: usize) -> usize {
+        offset + self.prefix_len() + self.crlf.partition_point(|newline| *newline < offset)
+    }
+
+    /// Gives `text`, formatted from the normalized copy,
+    /// the byte order mark and the line ending of the output.
+    pub(crate) fn restore(&self, text: &str) -> String {
+        let text = self.restore_line_endings(text);
+
+        if self.bom { format!("{BOM}{text}") } else { text }
+    }
+
+    /// Gives `text`, a formatted part of the normalized copy,
+    /// the line ending of the output.
+    pub(crate) fn restore_line_endings(&self, text: &str) -> String {
+        match self.line_ending {
+            LineEnding::Crlf => text.replace('\n', "\r\n"),
+            _ => text.to_string(),
+        }
+    }
+}
@@ -0,0 +1,107 @@
+/// The line width used unless another one is given.
+pub const DEFAULT_LINE_WIDTH: usize = 120;
+
+/// The indent width used unless another one is given.
+pub const DEFAULT_INDENT_WIDTH: usize = 2;
+
+/// Options to customize the formatting.
+///
+/// Built from [`FormatOptions::default`] and the `with_*` methods,
+/// so that new options can be added without breaking callers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct FormatOptions {
+    /// Maximum number of characters in a line. Code that does not fit in the
+    /// rest of the line is laid out vertically, across several lines.
+    ///
+    /// Lines can still be longer, if their code cannot be split any further.
+    pub line_width:    usize,
+    /// Number of spaces per level of indentation,
+    /// or the number of columns that a tab takes when indenting with tabs.
+    pub indent_width:  usize,
+    /// Whether to indent with spaces or with tabs.
+    pub indent_style:  IndentStyle,
+    /// How lines end in the output.
+    ///
+    /// The input can end its lines either way,
+    /// and a byte order mark at its start is kept in the output.
+    pub line_ending:   LineEnding,
+    /// Whether the output ends with a line ending.
+    pub final_newline: bool,
+}
+
+impl Default for FormatOptions {
+    fn default() -> FormatOptions {
+        FormatOptions {
+            line_width:    DEFAULT_LINE_WIDTH,
+            indent_width:  DEFAULT_INDENT_WIDTH,
+            indent_style:  IndentStyle::Spaces,
+            line_ending:   LineEnding::Auto,
+            final_newline: true,
+        }
+    }
+}
+
+impl FormatOptions {
+    /// Sets [`FormatOptions::line_width`].
+    pub fn with_line_width(mut self, line_width: usize) -> FormatOptions {
+        self.line_width = line_width;
+        self
+    }
+
+    /// Sets [`FormatOptions::indent_width`].
+    pub fn with_indent_width(mut self, indent_width: usize) -> FormatOptions {
+        self.indent_width = indent_width;
+        self
+    }
+
+    /// Sets [`FormatOptions::indent_style`].
+    pub fn with_indent_style(mut self, indent_style: IndentStyle) -> FormatOptions {
+        self.indent_style = indent_style;
+        self
+    }
+
+    /// Sets [`FormatOptions::line_ending`].
+    pub fn with_line_ending(mut self, line_ending: LineEnding) -> FormatOptions {
+        self.line_ending = line_ending;
+        self
+    }
+
+    /// Sets [`FormatOptions::final_newline`].
+    pub fn with_final_newline(mut self, final_newline: bool) -> FormatOptions {
+        self.final_newline = final_newline;
+        self
+    }
+
+    /// The whitespace that indents `levels` levels.
+    pub(crate) fn indent(&self, levels: usize) -> String {
+        match self.indent_style {
+            IndentStyle::Spaces => " ".repeat(levels * self.indent_width),
+            IndentStyle::Tabs => "\t".repeat(levels),
+        }
+    }
+}
+
+/// What to indent code with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IndentStyle {
+    /// [`FormatOptions::indent_width`] spaces per level.
+    Spaces,
+    /// A tab per level.
+    ///
+    /// The content of indented strings is still indented with spaces,
+    /// because Nix strips only spaces from the start of their lines.
+    Tabs,
+}
+
+/// The characters that end each line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineEnding {
+    /// The line ending of most lines of the input,
+    /// or `\n` if there are as many of each.
+    Auto,
+    /// `\n`, as in Unix.
+    Lf,
+    /// `\r\n`, as in Windows.
+    Crlf,
+}
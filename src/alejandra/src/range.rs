@@ -0,0 +1,93 @@
+/// Finds the smallest nodes that enclose `range` and can be formatted on
+/// their own.
+///
+/// Usually that is a single node, but when `range` only touches some of the
+/// items of a set, a list or a `let` block, then just those items are
+/// returned. Nothing is returned if `range` only touches the comments and
+/// whitespace around the top-level expression, or is past the end.
+pub(crate) fn enclosing_nodes(root: &rnix::SyntaxNode, range: rnix::TextRange) -> Vec<rnix::SyntaxNode> {
+    let range = match range.intersect(root.text_range()) {
+        Some(range) => range,
+        None => return Vec::new(),
+    };
+
+    let mut node = match root.covering_element(range) {
+        rnix::SyntaxElement::Node(node) => node,
+        rnix::SyntaxElement::Token(token) => token.parent(),
+    };
+
+    while !can_be_formatted_alone(&node) {
+        match node.parent() {
+            Some(parent) => node = parent,
+            None => break,
+        }
+    }
+
+    if matches!(
+        node.kind(),
+        rnix::SyntaxKind::NODE_ATTR_SET
+            | rnix::SyntaxKind::NODE_LET_IN
+            | rnix::SyntaxKind::NODE_LIST
+            | rnix::SyntaxKind::NODE_ROOT
+    ) {
+        let items: Vec<rnix::SyntaxNode> = node
+            .children()
+            .filter(|child| overlaps(child.text_range(), range))
+            .collect();
+
+        if !items.is_empty() || node.kind() == rnix::SyntaxKind::NODE_ROOT {
+            return items;
+        }
+    }
+
+    vec![node]
+}
+
+/// Number of indentation levels of the line where `offset` is,
+/// counting a level per tab, or per `indent_width` spaces.
+pub(crate) fn indentation_at(source: &str, offset: usize, indent_width: usize) -> usize {
+    let line_start = source[..offset].rfind('\n').map_or(0, |index| index + 1);
+    let indentation: Vec<char> = source[line_start..]
+        .chars()
+        .take_while(|char| *char == ' ' || *char == '\t')
+        .collect();
+
+    let tabs = indentation.iter().filter(|char| **char == '\t').count();
+    let spaces = indentation.len() - tabs;
+
+    tabs + spaces / indent_width.max(1)
+}
+
+fn can_be_formatted_alone(node: &rnix::SyntaxNode) -> bool {
+    // Strings are formatted as a whole,
+    // their interpolations depend on the surrounding text.
+    if node
+        .ancestors()
+        .skip(1)
+        .any(|ancestor| ancestor.kind() == rnix::SyntaxKind::NODE_STRING)
+    {
+        return false;
+    }
+
+    !matches!(
+        node.kind(),
+        rnix::SyntaxKind::NODE_DYNAMIC
+            | rnix::SyntaxKind::NODE_ERROR
+            | rnix::SyntaxKind::NODE_INHERIT_FROM
+            | rnix::SyntaxKind::NODE_KEY
+            | rnix::SyntaxKind::NODE_PAT_BIND
+            | rnix::SyntaxKind::NODE_PAT_ENTRY
+            | rnix::SyntaxKind::NODE_STRING_INTERPOL
+    ) && !node
+        .ancestors()
+        .skip(1)
+        .any(|ancestor| ancestor.kind() == rnix::SyntaxKind::NODE_KEY)
+}
+
+fn overlaps(a: rnix::TextRange, b: rnix::TextRange) -> bool {
+    if b.is_empty() {
+        a.contains_inclusive(b.start())
+    } else {
+        a.start() < b.end() && b.start() < a.end()
+    }
+}
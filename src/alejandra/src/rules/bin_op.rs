@@ -97,7 +97,17 @@ pub(crate) fn rule_with_configuration(
     }
 
     // third
-    if vertical {
+    //
+    // The rest of a chain of the same operator, like `a or b or c`,
+    // is laid out vertically too.
+    let continues_chain = parent_kind == "bin_op_and_or_default"
+        && third.element.kind() == node.kind()
+        && third
+            .element
+            .as_node()
+            .and_then(operator)
+            .is_some_and(|third| Some(third) == operator(node));
+    if vertical && !(build_ctx.vertical && continues_chain) {
         steps.push_back(crate::builder::Step::FormatWider(third.element));
     } else {
         steps.push_back(crate::builder::Step::Format(third.element));
@@ -105,3 +115,16 @@ pub(crate) fn rule_with_configuration(
 
     steps
 }
+
+/// The text of the operator of a binary operation.
+fn operator(node: &rnix::SyntaxNode) -> Option<String> {
+    node.children_with_tokens()
+        .filter_map(|child| child.into_token())
+        .find(|token| {
+            !matches!(
+                token.kind(),
+                rnix::SyntaxKind::TOKEN_COMMENT | rnix::SyntaxKind::TOKEN_WHITESPACE
+            )
+        })
+        .map(|token| token.text().to_string())
+}
@@ -14,7 +14,7 @@ pub(crate) fn rule(
     let else_expr = children.next().unwrap();
 
     // if_
-    steps.push_back(crate::builder::Step::Format(if_.element));
+    steps.push_back(crate::builder::Step::Format(if_.element.clone()));
 
     steps.push_back(crate::builder::Step::Indent);
     if let Some(text) = if_.inline_comment {
@@ -37,7 +37,8 @@ pub(crate) fn rule(
     // if_expr
     if !if_.has_inline_comment
         && !if_.has_comments
-        && crate::builder::fits_in_single_line(build_ctx, if_expr.element.clone())
+        && (fits_after(build_ctx, false, if_.element.clone(), &if_expr.element)
+            || !fits_below(build_ctx, false, if_.element, &if_expr.element))
     {
         steps.push_back(crate::builder::Step::Whitespace);
         steps.push_back(crate::builder::Step::FormatWider(if_expr.element));
@@ -71,7 +72,7 @@ pub(crate) fn rule(
     }
 
     // then_
-    steps.push_back(crate::builder::Step::Format(then_.element));
+    steps.push_back(crate::builder::Step::Format(then_.element.clone()));
 
     steps.push_back(crate::builder::Step::Indent);
     if let Some(text) = then_.inline_comment {
@@ -100,7 +101,8 @@ pub(crate) fn rule(
                 | rnix::SyntaxKind::NODE_LET_IN
                 | rnix::SyntaxKind::NODE_LIST
                 | rnix::SyntaxKind::NODE_STRING
-        ) || crate::builder::fits_in_single_line(build_ctx, then_expr.element.clone()))
+        ) || fits_after(build_ctx, true, then_.element.clone(), &then_expr.element)
+            || !fits_below(build_ctx, true, then_.element, &then_expr.element))
     {
         steps.push_back(crate::builder::Step::Whitespace);
         steps.push_back(crate::builder::Step::FormatWider(then_expr.element));
@@ -134,7 +136,7 @@ pub(crate) fn rule(
     }
 
     // else_
-    steps.push_back(crate::builder::Step::Format(else_.element));
+    steps.push_back(crate::builder::Step::Format(else_.element.clone()));
 
     steps.push_back(crate::builder::Step::Indent);
     if let Some(text) = else_.inline_comment {
@@ -164,7 +166,8 @@ pub(crate) fn rule(
                 | rnix::SyntaxKind::NODE_LET_IN
                 | rnix::SyntaxKind::NODE_LIST
                 | rnix::SyntaxKind::NODE_STRING
-        ) || crate::builder::fits_in_single_line(build_ctx, else_expr.element.clone()))
+        ) || fits_after(build_ctx, true, else_.element.clone(), &else_expr.element)
+            || !fits_below(build_ctx, true, else_.element, &else_expr.element))
     {
         steps.push_back(crate::builder::Step::Whitespace);
         steps.push_back(crate::builder::Step::FormatWider(else_expr.element));
@@ -178,3 +181,46 @@ pub(crate) fn rule(
 
     steps
 }
+
+/// Whether `expr` fits in a single line after `keyword` and a space,
+/// where `keyword` starts a new line if `on_new_line`.
+fn fits_after(
+    build_ctx: &crate::builder::BuildCtx,
+    on_new_line: bool,
+    keyword: rnix::SyntaxElement,
+    expr: &rnix::SyntaxElement,
+) -> bool {
+    let mut steps = keyword_steps(on_new_line, keyword);
+    steps.push(crate::builder::Step::Whitespace);
+
+    crate::builder::fits_in_single_line(&crate::builder::trial_after(build_ctx, &steps), expr.clone())
+}
+
+/// Whether `expr` fits in the line width on the lines after `keyword`,
+/// where moving it is only worth it if it does.
+fn fits_below(
+    build_ctx: &crate::builder::BuildCtx,
+    on_new_line: bool,
+    keyword: rnix::SyntaxElement,
+    expr: &rnix::SyntaxElement,
+) -> bool {
+    let mut steps = keyword_steps(on_new_line, keyword);
+    steps.push(crate::builder::Step::Indent);
+    steps.push(crate::builder::Step::NewLine);
+    steps.push(crate::builder::Step::Pad);
+    steps.push(crate::builder::Step::FormatWider(expr.clone()));
+    steps.push(crate::builder::Step::Dedent);
+
+    crate::builder::fits_in_width(build_ctx, &steps)
+}
+
+/// `keyword`, after a new line if `on_new_line`.
+fn keyword_steps(on_new_line: bool, keyword: rnix::SyntaxElement) -> Vec<crate::builder::Step> {
+    let mut steps = Vec::new();
+    if on_new_line {
+        steps.push(crate::builder::Step::NewLine);
+        steps.push(crate::builder::Step::Pad);
+    }
+    steps.push(crate::builder::Step::Format(keyword));
+    steps
+}
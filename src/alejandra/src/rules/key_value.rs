@@ -12,6 +12,7 @@ pub(crate) fn rule(build_ctx: &BuildCtx, node: &rnix::SyntaxNode) -> LinkedList<
 
     // a
     let child = children.get_next().unwrap();
+    let child_key = child.clone();
     if vertical {
         steps.push_back(BuildStep::FormatWider(child));
     } else {
@@ -80,7 +81,7 @@ pub(crate) fn rule(build_ctx: &BuildCtx, node: &rnix::SyntaxNode) -> LinkedList<
 
     // =
     let mut dedent = false;
-    steps.push_back(BuildStep::Format(child_equal));
+    steps.push_back(BuildStep::Format(child_equal.clone()));
 
     if vertical {
         use rnix::SyntaxKind::*;
@@ -99,8 +100,18 @@ pub(crate) fn rule(build_ctx: &BuildCtx, node: &rnix::SyntaxNode) -> LinkedList<
 
         let node_is_apply = matches!(child_expr.kind(), NODE_APPLY);
 
-        let snd_thru_penult_indented =
-            crate::utils::second_through_penultimate_line_are_indented(build_ctx, child_expr.clone(), false);
+        // Where the value starts when it stays on the same line.
+        let snd_thru_penult_indented = node_is_apply && {
+            let mut build_ctx = crate::builder::trial_after(build_ctx, &[
+                BuildStep::FormatWider(child_key),
+                BuildStep::Whitespace,
+                BuildStep::Format(child_equal),
+                BuildStep::Whitespace,
+            ]);
+            build_ctx.vertical = !crate::builder::fits_in_single_line(&build_ctx, child_expr.clone());
+
+            crate::utils::second_through_penultimate_line_are_indented(&build_ctx, child_expr.clone(), false)
+        };
 
         // FIXME: how the FUCK does this entire if chain work. what the fuck.
         if !comments_before.is_empty() || !comments_after.is_empty() {
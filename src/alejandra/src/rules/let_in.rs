@@ -19,9 +19,26 @@ pub(crate) fn rule(build_ctx: &BuildCtx, node: &rnix::SyntaxNode) -> LinkedList<
     let vertical =
         items_count > 1 || children.has_comments() || children.has_newlines() || build_ctx.vertical;
 
+    // `then let` and `else let` stay on the line of their keyword.
+    let after_then_or_else = std::iter::successors(node.prev_sibling_or_token(), |element| {
+        element.prev_sibling_or_token()
+    })
+    .find(|element| {
+        !matches!(
+            element.kind(),
+            rnix::SyntaxKind::TOKEN_COMMENT | rnix::SyntaxKind::TOKEN_WHITESPACE
+        )
+    })
+    .is_some_and(|element| {
+        matches!(
+            element.kind(),
+            rnix::SyntaxKind::TOKEN_THEN | rnix::SyntaxKind::TOKEN_ELSE
+        )
+    });
+
     // `let` in `let <bindings> in <expr>
     let child = children.get_next().unwrap();
-    if vertical {
+    if vertical && !after_then_or_else {
         // For expanded `let`s, put the `let` on a new line.
         // FIXME: what will this do for a file that starts immediately with a `let`?
         steps.push_back(BuildStep::NewLine);
@@ -62,10 +62,10 @@ pub(crate) fn rule(build_ctx: &BuildCtx, node: &rnix::SyntaxNode) -> LinkedList<
                 use LookState::*;
                 let mut found_newline = found_newline;
 
-                if matches!(element.kind(), TOKEN_WHITESPACE) {
-                    if element.as_token().unwrap().text().contains("\n") {
-                        return Almost;
-                    }
+                if matches!(element.kind(), TOKEN_WHITESPACE)
+                    && element.as_token().unwrap().text().contains('\n')
+                {
+                    return Almost;
                 }
 
                 if let Some(node) = element.as_node() {
@@ -98,7 +98,10 @@ pub(crate) fn rule(build_ctx: &BuildCtx, node: &rnix::SyntaxNode) -> LinkedList<
             }
 
             match look_for_newline_until_func_end(&expression.element, false) {
-                LookState::Almost => false,
+                // A loose lambda that does not fit gets a newline after its
+                // arguments, which would make it tight on the next pass, so
+                // keep it loose if it starts on a new line.
+                LookState::Almost => opener.has_trivialities,
                 LookState::Done(found_newline) => {
                     dbg!(found_newline);
                     found_newline
@@ -143,7 +146,9 @@ pub(crate) fn rule(build_ctx: &BuildCtx, node: &rnix::SyntaxNode) -> LinkedList<
         node_can_indent && !crate::utils::second_through_penultimate_line_are_indented(
             build_ctx,
             expression.element.clone(),
-            matches!(expr_kind, NODE_LAMBDA),
+            // A loose lambda is on its own line, and gets indented like
+            // anything else that is.
+            matches!(expr_kind, NODE_LAMBDA) && !loose,
         )
     };
 
@@ -13,6 +13,7 @@ pub(crate) fn rule(build_ctx: &BuildCtx, node: &SyntaxNode) -> LinkedList<BuildS
 
     // expr
     let child = children.get_next().unwrap();
+    let child_name = child.clone();
     if vertical {
         steps.push_back(BuildStep::FormatWider(child));
     } else {
@@ -41,6 +42,7 @@ pub(crate) fn rule(build_ctx: &BuildCtx, node: &SyntaxNode) -> LinkedList<BuildS
 
         // operator
         let child = children.get_next().unwrap();
+        let child_operator = child.clone();
         steps.push_back(BuildStep::Format(child));
 
         // /**/
@@ -75,8 +77,16 @@ pub(crate) fn rule(build_ctx: &BuildCtx, node: &SyntaxNode) -> LinkedList<BuildS
                     | NODE_LITERAL
                     | NODE_STRING,
             )
-        } || crate::builder::fits_in_single_line(build_ctx, child.clone())
-        {
+        } || crate::builder::fits_in_single_line(
+            // Where the default starts when it stays on the same line.
+            &crate::builder::trial_after(build_ctx, &[
+                BuildStep::Format(child_name),
+                BuildStep::Whitespace,
+                BuildStep::Format(child_operator),
+                BuildStep::Whitespace,
+            ]),
+            child.clone(),
+        ) {
             steps.push_back(BuildStep::Whitespace);
         } else {
             dedent = true;
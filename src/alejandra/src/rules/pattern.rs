@@ -19,7 +19,7 @@ pub(crate) fn rule(build_ctx: &BuildCtx, node: &rnix::SyntaxNode) -> LinkedList<
         || !pattern.comments_before_end_at.is_empty();
 
     let soft_len: u32 = node.text_range().len().into();
-    let indentation_size = 2; // FIXME: detect
+    let indentation_size = build_ctx.options.indent_width;
     let hard_len = (build_ctx.indentation as u32 * indentation_size as u32) + soft_len;
 
     let arguments_count = pattern.arguments.len();
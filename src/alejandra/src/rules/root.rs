@@ -29,9 +29,18 @@ pub(crate) fn rule(
     }
 
     // Trailing newline
-    if let Some(last_step) = steps.back() {
-        if *last_step != crate::builder::Step::NewLine {
-            steps.push_back(crate::builder::Step::NewLine);
+    if build_ctx.options.final_newline {
+        if let Some(last_step) = steps.back() {
+            if *last_step != crate::builder::Step::NewLine {
+                steps.push_back(crate::builder::Step::NewLine);
+            }
+        }
+    } else {
+        while matches!(
+            steps.back(),
+            Some(crate::builder::Step::NewLine | crate::builder::Step::Pad)
+        ) {
+            steps.pop_back();
         }
     }
 
@@ -82,13 +82,14 @@ pub(crate) fn rule(
             })
             .collect();
 
-        // Indent everything 2 spaces
+        // Indent everything one level
         if lines.len() > 1 && lines.iter().filter(|line| !line.trim().is_empty()).count() >= 1 {
+            let padding = build_ctx.options.indent(1);
             lines = lines
                 .iter()
                 .map(|line| {
                     if !line.trim().is_empty() {
-                        format!("  {}", line)
+                        format!("{}{}", padding, line)
                     } else {
                         line.to_string()
                     }
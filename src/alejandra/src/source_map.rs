@@ -0,0 +1,115 @@
+/// A token that was copied from the input to the output.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Entry {
+    old: usize,
+    new: usize,
+    len: usize,
+}
+
+/// Maps byte offsets in the input of the formatter
+/// to byte offsets in its output,
+/// for instance to keep the cursor of an editor at the same spot.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SourceMap {
+    entries: Vec<Entry>,
+    old_len: usize,
+    new_len: usize,
+}
+
+impl SourceMap {
+    /// A map where every offset maps to itself,
+    /// used when the input was not modified.
+    pub fn identity(len: usize) -> SourceMap {
+        SourceMap {
+            entries: vec![Entry { old: 0, new: 0, len }],
+            old_len: len,
+            new_len: len,
+        }
+    }
+
+    /// Translates `old`, a byte offset in the input,
+    /// to the equivalent byte offset in the output.
+    ///
+    /// Offsets inside of a token map to the same spot of that token.
+    /// Offsets between tokens map to the end of the previous token
+    /// if they are right after it,
+    /// or to the start of the next token otherwise.
+    pub fn map_offset(&self, old: usize) -> usize {
+        let old = old.min(self.old_len);
+        let next = self.entries.partition_point(|entry| entry.old <= old);
+
+        if next > 0 {
+            let previous = &self.entries[next - 1];
+
+            if old <= previous.old + previous.len {
+                return previous.new + (old - previous.old);
+            }
+        }
+
+        match self.entries.get(next) {
+            Some(entry) => entry.new,
+            None => {
+                let previous_end = self.entries.last().map_or(0, |entry| entry.new + entry.len);
+
+                previous_end
+                    .max(self.new_len.saturating_sub(self.old_len - old))
+                    .min(self.new_len)
+            }
+        }
+    }
+
+    pub(crate) fn new(old_len: usize) -> SourceMap {
+        SourceMap {
+            entries: Vec::new(),
+            old_len,
+            new_len: 0,
+        }
+    }
+
+    /// Records that `text` was emitted,
+    /// and that it was copied from `old` in the input, if given.
+    pub(crate) fn emit(&mut self, old: Option<usize>, text: &str) {
+        if let Some(old) = old {
+            self.entries.push(Entry {
+                old,
+                new: self.new_len,
+                len: text.len(),
+            });
+        }
+
+        self.new_len += text.len();
+    }
+
+    /// Accounts for the input and the output being normalized copies,
+    /// see [`crate::normalize::Normalized`]:
+    /// the real ones start with `prefix_len` more bytes,
+    /// and each `\n` at the offsets in `old_crlf` and `new_crlf`
+    /// is really a `\r\n`.
+    pub(crate) fn denormalize(
+        mut self,
+        prefix_len: usize,
+        old_crlf: &[usize],
+        new_crlf: &[usize],
+    ) -> SourceMap {
+        let crlf_before = |crlf: &[usize], offset: usize| crlf.partition_point(|newline| *newline < offset);
+
+        for entry in self.entries.iter_mut() {
+            let before = crlf_before(new_crlf, entry.new);
+            let within = crlf_before(new_crlf, entry.new + entry.len) - before;
+
+            entry.old += prefix_len + crlf_before(old_crlf, entry.old);
+            entry.new += prefix_len + before;
+            entry.len += within;
+        }
+        self.old_len += prefix_len + old_crlf.len();
+        self.new_len += prefix_len + new_crlf.len();
+
+        self
+    }
+
+    pub(crate) fn finish(mut self) -> SourceMap {
+        self.entries.sort_by_key(|entry| entry.old);
+        self.entries.dedup_by_key(|entry| entry.old);
+        self
+    }
+}
@@ -13,6 +13,8 @@ pub(crate) fn second_through_penultimate_line_are_indented(
 ) -> bool {
     let mut build_ctx = crate::builder::BuildCtx {
         force_wide: false,
+        pos_new: build_ctx.trial_pos_new(),
+        source_map: None,
         ..build_ctx.clone()
     };
 
@@ -26,9 +28,10 @@ pub(crate) fn second_through_penultimate_line_are_indented(
         return if_leq_than_two_lines;
     }
 
-    let whitespace = format!("{0:<1$}  ", "", 2 * build_ctx.indentation);
-    let lambda = format!("{0:<1$}}}:", "", 2 * build_ctx.indentation);
-    let in_ = format!("{0:<1$}in", "", 2 * build_ctx.indentation);
+    let indentation = build_ctx.options.indent(build_ctx.indentation);
+    let whitespace = build_ctx.options.indent(build_ctx.indentation + 1);
+    let lambda = format!("{indentation}}}:");
+    let in_ = format!("{indentation}in");
 
     formatted_lines.iter().skip(1).rev().skip(1).all(|line| {
         line.is_empty()
@@ -38,6 +41,7 @@ pub(crate) fn second_through_penultimate_line_are_indented(
     })
 }
 
+#[allow(dead_code)] // These are used for debugging.
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct FormatSyntaxOptions {
     pub short: bool,
@@ -53,12 +57,14 @@ impl Default for FormatSyntaxOptions {
     }
 }
 
+#[allow(dead_code)]
 impl FormatSyntaxOptions {
     pub fn log_for<T: FormatSyntax>(self, element: &T, level: log::Level) {
         element.log_syn(level, self)
     }
 }
 
+#[allow(dead_code)]
 pub trait FormatSyntax {
     type Options;
 
@@ -126,7 +132,6 @@ impl FormatSyntax for rnix::SyntaxNode {
                         .next()
                         .unwrap()
                         .chars()
-                        .into_iter()
                         .skip_while(|ch| ch.is_whitespace())
                         .take(Ord::max(MIN_PREVIEW_LEN, MAX_LINE_LEN - current_len as i32) as usize)
                         .collect();
@@ -138,7 +143,7 @@ impl FormatSyntax for rnix::SyntaxNode {
                     format!("`\n{element_text}\n`")
                 }
             } else {
-                format!("{element_text}")
+                element_text
             }
         };
 
@@ -165,13 +170,13 @@ impl FormatSyntax for rnix::SyntaxToken {
 
         if let rnix::SyntaxKind::TOKEN_WHITESPACE = self.kind() {
             let mut whitespaces = String::new();
-            for byte in self.text().as_bytes().into_iter().copied() {
+            for byte in self.text().as_bytes().iter().copied() {
                 if byte == 0x20 {
                     // Show spaces as unicode ␠.
-                    whitespaces.push_str("␠");
+                    whitespaces.push('␠');
                 } else if byte == 0x0A {
                     // Show newlines as unicode ␤
-                    whitespaces.push_str("␤");
+                    whitespaces.push('␤');
                 } else {
                     // Otherwise, show it as an escape sequence.
                     whitespaces.push_str(&format!(r"\x{:02x}", byte));
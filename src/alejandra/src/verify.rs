@@ -0,0 +1,228 @@
+use crate::normalize::Normalized;
+use crate::options::LineEnding;
+
+/// Placeholder for interpolations while normalizing indented strings.
+const INTERPOLATION: char = '\u{0}';
+
+#[derive(Debug, PartialEq)]
+enum Item {
+    Start(rnix::SyntaxKind),
+    Finish,
+    Token(rnix::SyntaxKind, String),
+}
+
+/// Checks that `before` and `after` parse to the same syntax tree,
+/// ignoring whitespace, comments, commas and redundant parentheses.
+///
+/// On divergence returns a description of the first difference.
+pub(crate) fn equivalent(before: &str, after: &str) -> Result<(), String> {
+    // Like the formatter, ignore the byte order mark and `\r\n`.
+    let before = &normalized(before);
+    let after = &normalized(after);
+
+    let before_items = items(before).map_err(|error| format!("the input does not parse: {error}"))?;
+    let after_items = items(after).map_err(|error| format!("the output does not parse: {error}"))?;
+
+    let mut after_iter = after_items.iter();
+    for (offset_before, item_before) in &before_items {
+        match after_iter.next() {
+            Some((_, item_after)) if item_after == item_before => {}
+            Some((offset_after, item_after)) => {
+                return Err(format!(
+                    "{} at {} of the input became {} at {} of the output",
+                    describe(item_before),
+                    crate::error::Location::from_offset(before, *offset_before),
+                    describe(item_after),
+                    crate::error::Location::from_offset(after, *offset_after),
+                ));
+            }
+            None => {
+                return Err(format!(
+                    "{} at {} of the input is missing from the output",
+                    describe(item_before),
+                    crate::error::Location::from_offset(before, *offset_before),
+                ));
+            }
+        }
+    }
+
+    match after_iter.next() {
+        Some((offset_after, item_after)) => Err(format!(
+            "{} at {} of the output is not in the input",
+            describe(item_after),
+            crate::error::Location::from_offset(after, *offset_after),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Number of unchanged lines shown before a difference.
+const CONTEXT_LINES: usize = 2;
+
+/// Describes the first line that differs between the first and the second
+/// pass of the formatter, with some context.
+pub(crate) fn first_difference(first: &str, second: &str) -> Option<String> {
+    let first_lines: Vec<&str> = first.split('\n').collect();
+    let second_lines: Vec<&str> = second.split('\n').collect();
+
+    let index = (0..first_lines.len().max(second_lines.len()))
+        .find(|index| first_lines.get(*index) != second_lines.get(*index))?;
+
+    let mut description = format!("the second pass changes line {}:", index + 1);
+    for (context, line) in first_lines
+        .iter()
+        .enumerate()
+        .take(index)
+        .skip(index.saturating_sub(CONTEXT_LINES))
+    {
+        description.push_str(&format!("\n  {:>5} | {line}", context + 1));
+    }
+    if let Some(line) = first_lines.get(index) {
+        description.push_str(&format!("\n- {:>5} | {line}", index + 1));
+    }
+    if let Some(line) = second_lines.get(index) {
+        description.push_str(&format!("\n+ {:>5} | {line}", index + 1));
+    }
+
+    Some(description)
+}
+
+/// `text` without its byte order mark, and with `\n` line endings.
+pub(crate) fn normalized(text: &str) -> String {
+    Normalized::new(text, LineEnding::Lf).text
+}
+
+fn describe(item: &Item) -> String {
+    match item {
+        Item::Start(kind) => format!("{kind:?}"),
+        Item::Finish => "the end of a node".to_string(),
+        Item::Token(kind, text) => format!("{kind:?} {text:?}"),
+    }
+}
+
+/// Flattens the syntax tree of `source`, with the offset of each item.
+fn items(source: &str) -> Result<Vec<(usize, Item)>, String> {
+    let ast = rnix::parser::parse(rnix::tokenizer::Tokenizer::new(source));
+
+    if let Some(error) = ast.errors().first() {
+        return Err(crate::error::ParseError::new(source, error).to_string());
+    }
+
+    let mut items = Vec::new();
+    flatten(&mut items, &ast.node());
+    Ok(items)
+}
+
+fn flatten(items: &mut Vec<(usize, Item)>, node: &rnix::SyntaxNode) {
+    let offset = node.text_range().start().into();
+
+    match node.kind() {
+        rnix::SyntaxKind::NODE_PAREN => {
+            // Parentheses only group, and grouping is already
+            // captured by the shape of the tree.
+            for child in node.children() {
+                flatten(items, &child);
+            }
+            return;
+        }
+        rnix::SyntaxKind::NODE_STRING if is_indented_string(node) => {
+            items.push((offset, Item::Start(node.kind())));
+            flatten_indented_string(items, node);
+            items.push((node.text_range().end().into(), Item::Finish));
+            return;
+        }
+        _ => {}
+    }
+
+    items.push((offset, Item::Start(node.kind())));
+    for child in node.children_with_tokens() {
+        match child {
+            rnix::SyntaxElement::Node(node) => flatten(items, &node),
+            rnix::SyntaxElement::Token(token) => {
+                if !matches!(
+                    token.kind(),
+                    rnix::SyntaxKind::TOKEN_COMMA
+                        | rnix::SyntaxKind::TOKEN_COMMENT
+                        | rnix::SyntaxKind::TOKEN_WHITESPACE
+                ) {
+                    items.push((
+                        token.text_range().start().into(),
+                        Item::Token(token.kind(), token.text().to_string()),
+                    ));
+                }
+            }
+        }
+    }
+    items.push((node.text_range().end().into(), Item::Finish));
+}
+
+fn is_indented_string(node: &rnix::SyntaxNode) -> bool {
+    node.first_token().is_some_and(|token| token.text() == "''")
+}
+
+/// Indented strings are re-indented while formatting,
+/// so compare the value they evaluate to instead of their text.
+fn flatten_indented_string(items: &mut Vec<(usize, Item)>, node: &rnix::SyntaxNode) {
+    let offset = node.text_range().start().into();
+
+    let mut content = String::new();
+    let mut interpolations = Vec::new();
+    for child in node.children_with_tokens() {
+        match child {
+            rnix::SyntaxElement::Node(node) => {
+                content.push(INTERPOLATION);
+                interpolations.push(node);
+            }
+            rnix::SyntaxElement::Token(token) => {
+                if token.kind() == rnix::SyntaxKind::TOKEN_STRING_CONTENT {
+                    content.push_str(token.text());
+                }
+            }
+        }
+    }
+
+    let mut interpolations = interpolations.into_iter();
+    for (index, literal) in strip_indentation(&content).split(INTERPOLATION).enumerate() {
+        if index > 0 {
+            if let Some(interpolation) = interpolations.next() {
+                flatten(items, &interpolation);
+            }
+        }
+        items.push((
+            offset,
+            Item::Token(rnix::SyntaxKind::TOKEN_STRING_CONTENT, literal.to_string()),
+        ));
+    }
+}
+
+/// Mimics how Nix strips the indentation of indented strings.
+fn strip_indentation(content: &str) -> String {
+    let mut lines: Vec<&str> = content.split('\n').collect();
+
+    // The first line is dropped if it is blank,
+    // and so are the spaces of the last one.
+    if lines.len() > 1 && lines[0].chars().all(|char| char == ' ') {
+        lines.remove(0);
+    }
+    if let Some(last) = lines.last_mut() {
+        if last.chars().all(|char| char == ' ') {
+            *last = "";
+        }
+    }
+
+    let indentation = lines
+        .iter()
+        .filter(|line| !line.chars().all(|char| char == ' '))
+        .map(|line| line.len() - line.trim_start_matches(' ').len())
+        .min()
+        .unwrap_or(0);
+
+    lines
+        .iter()
+        .map(|line| {
+            let spaces = line.len() - line.trim_start_matches(' ').len();
+            &line[spaces.min(indentation)..]
+        })
+        .collect::<Vec<&str>>()
+        .join("\n")
+}
@@ -0,0 +1,78 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// How many names to try for the temporary file
+/// before giving up.
+const TEMPORARY_FILE_ATTEMPTS: usize = 100;
+
+/// Replaces the content of the file at `path` with `content`,
+/// so that readers see either the old or the new content, never a mix.
+///
+/// The content is written to a temporary file in the same directory,
+/// which is then renamed over the original. Symbolic links are followed,
+/// so the file they point to is the one replaced, and its permissions are
+/// preserved, as well as its owner when possible.
+///
+/// If `backup_suffix` is given, the original is kept in a copy whose name
+/// is the one of the file followed by the suffix.
+pub fn atomically(path: &Path, content: &str, backup_suffix: Option<&str>) -> std::io::Result<()> {
+    let path = std::fs::canonicalize(path)?;
+    let metadata = std::fs::metadata(&path)?;
+
+    if let Some(backup_suffix) = backup_suffix {
+        let mut backup = path.clone().into_os_string();
+        backup.push(backup_suffix);
+        std::fs::copy(&path, backup)?;
+    }
+
+    let (temporary_path, mut temporary_file) = create_temporary_file(&path)?;
+
+    let result = temporary_file
+        .write_all(content.as_bytes())
+        .and_then(|()| temporary_file.set_permissions(metadata.permissions()))
+        .and_then(|()| temporary_file.sync_all())
+        .and_then(|()| {
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::MetadataExt;
+
+                // Only the superuser can give files away, so this is allowed to fail.
+                let _ =
+                    std::os::unix::fs::fchown(&temporary_file, Some(metadata.uid()), Some(metadata.gid()));
+            }
+
+            std::fs::rename(&temporary_path, &path)
+        });
+
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temporary_path);
+    }
+
+    result
+}
+
+/// Creates a new file next to `path`, with a name that no other file has.
+fn create_temporary_file(path: &Path) -> std::io::Result<(PathBuf, File)> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+
+    let mut attempt = 0;
+    loop {
+        let temporary_path = path.with_file_name(format!(".{name}.{}.{attempt}.tmp", std::process::id()));
+
+        match File::options()
+            .write(true)
+            .create_new(true)
+            .open(&temporary_path)
+        {
+            Ok(file) => return Ok((temporary_path, file)),
+            Err(error)
+                if error.kind() == std::io::ErrorKind::AlreadyExists && attempt < TEMPORARY_FILE_ATTEMPTS =>
+            {
+                attempt += 1;
+            }
+            Err(error) => return Err(error),
+        }
+    }
+}
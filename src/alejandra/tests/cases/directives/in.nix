@@ -0,0 +1,25 @@
+{
+  licenses = {
+    # alejandra: off
+    mit      = { spdx = "MIT";      free = true;  };
+    unfree   = { spdx = null;       free = false; };
+    # alejandra: on
+    asl20 = {   spdx = "Apache-2.0"; };
+  };
+  matrix = [
+    # alejandra: skip
+    [ 1 0 0
+      0 1 0
+      0 0 1 ]
+    [ 1 2 ]
+  ];
+  identity = [
+    # alejandra: off
+    1  0  0
+    0  1  0
+    0  0  1
+    # alejandra: on
+  ];
+  x = ( # alejandra: skip
+  a  +  b );
+}
@@ -0,0 +1,7 @@
+{
+  short = { a = 1; b = 2; };
+  cargoDeps = rustPlatform.fetchCargoTarball { inherit src; name = "${pname}-${version}"; hash = "sha256-W4FyqwJpimf0isQRCq9TegpTQPQfsumx40AFQCFG5VQ="; };
+  buildInputs = [ openssl zlib libxml2 libxslt curl sqlite postgresql mysql boost icu readline ncurses gettext libffi pcre2 ];
+  description = "A string that is longer than the line width, but that is kept on its own line: ${pname}";
+  assertions = if config.boot.kernelPackages.kernel ? features then [] else let cfg = config.boot.kernelPackages.kernel.config; in map (attrs: { assertion = attrs.assertion cfg; inherit (attrs) message; }) config.system.requiredKernelConfig;
+}
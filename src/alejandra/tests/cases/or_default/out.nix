@@ -38,11 +38,44 @@
         or
         /**/
         c)))
-  (a.a or a.a or a.a or a.a or a.a or a.a or a.a or a.a or a.a or a.a or a.a or a.a or a.a or a.a or a.a or a.a or a.a or a.a or a.a)
+  (a.a
+    or a.a
+    or a.a
+    or a.a
+    or a.a
+    or a.a
+    or a.a
+    or a.a
+    or a.a
+    or a.a
+    or a.a
+    or a.a
+    or a.a
+    or a.a
+    or a.a
+    or a.a
+    or a.a
+    or a.a
+    or a.a)
   (a.a
     or a.a # test
     or a.a # test
     or # test
     a.a
-    or a.a or a.a or a.a or a.a or a.a or a.a or a.a or a.a or a.a or a.a or a.a or a.a or a.a or a.a or a.a or a.a)
+    or a.a
+    or a.a
+    or a.a
+    or a.a
+    or a.a
+    or a.a
+    or a.a
+    or a.a
+    or a.a
+    or a.a
+    or a.a
+    or a.a
+    or a.a
+    or a.a
+    or a.a
+    or a.a)
 ]
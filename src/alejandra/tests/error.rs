@@ -0,0 +1,111 @@
+use alejandra::error::Error;
+use alejandra::error::InternalError;
+use alejandra::error::Location;
+use alejandra::format::Status;
+use pretty_assertions::assert_eq;
+
+#[test]
+fn parse_error_has_position() {
+    let before = "{\n  a = 1;\n  b = ;\n}\n".to_string();
+    let (status, after) = alejandra::format::in_memory("test.nix".to_string(), before.clone());
+
+    assert_eq!(after, before);
+
+    match status {
+        Status::Error(Error::Parse(errors)) => {
+            assert_eq!(errors[0].start, Location { line: 3, column: 7 });
+            assert_eq!(&before[errors[0].range], ";");
+        }
+        status => panic!("expected a parse error, got: {status:?}"),
+    }
+}
+
+#[test]
+fn all_parse_errors_are_reported() {
+    let before = "{\n  a = ;\n  b = ;\n  c = ;\n}\n".to_string();
+    let (status, _) = alejandra::format::in_memory("test.nix".to_string(), before);
+
+    match status {
+        Status::Error(Error::Parse(errors)) => {
+            let lines: Vec<usize> = errors.iter().map(|error| error.start.line).collect();
+            assert!(lines.contains(&2) && lines.contains(&3) && lines.contains(&4));
+            assert!(
+                errors
+                    .windows(2)
+                    .all(|pair| pair[0].range.start() <= pair[1].range.start())
+            );
+        }
+        status => panic!("expected a parse error, got: {status:?}"),
+    }
+}
+
+#[test]
+fn check_syntax_reports_the_same_errors_as_formatting() {
+    let before = "{\r\n  a   = ;\r\n  b = ;\r\n}\r\n".to_string();
+    let (status, _) = alejandra::format::in_memory("test.nix".to_string(), before.clone());
+
+    match (status, alejandra::format::check_syntax(&before)) {
+        (Status::Error(Error::Parse(expected)), Err(Error::Parse(errors))) => {
+            assert_eq!(errors, expected);
+        }
+        (status, checked) => panic!("expected parse errors, got: {status:?} and {checked:?}"),
+    }
+
+    assert!(alejandra::format::check_syntax("{\na=1;\n}\n").is_ok());
+}
+
+#[test]
+fn internal_error_describes_where_it_happened() {
+    let mut error = InternalError {
+        message:  "rule apply failed".to_string(),
+        kind:     None,
+        location: None,
+    };
+
+    assert_eq!(error.to_string(), "rule apply failed");
+
+    error.kind = Some(rnix::SyntaxKind::NODE_APPLY);
+    error.location = Some(Location { line: 2, column: 3 });
+
+    assert_eq!(
+        error.to_string(),
+        "rule apply failed while formatting NODE_APPLY at 2:3"
+    );
+}
+
+#[test]
+fn internal_error_describes_a_panic() {
+    let payloads: [Box<dyn std::any::Any + Send>; 3] =
+        [Box::new("a str"), Box::new("a string".to_string()), Box::new(1)];
+    let messages: Vec<String> = payloads
+        .iter()
+        .map(|payload| InternalError::from_panic(payload.as_ref()).message)
+        .collect();
+
+    assert_eq!(messages, ["a str", "a string", "unknown panic"]);
+}
+
+#[test]
+fn internal_error_returns_the_input_unchanged() {
+    // rnix parses an empty interpolation without errors,
+    // but no rule expects it.
+    let before = "{ a   =   x.${ }; }\n".to_string();
+    let (status, after) = alejandra::format::in_memory("test.nix".to_string(), before.clone());
+
+    assert_eq!(after, before);
+
+    match status {
+        Status::InternalError(error) => {
+            assert_eq!(error.kind, Some(rnix::SyntaxKind::NODE_DYNAMIC));
+            assert_eq!(
+                error.location,
+                Some(Location {
+                    line:   1,
+                    column: 13,
+                })
+            );
+        }
+        status => panic!("expected an internal error, got: {status:?}"),
+    }
+}
+
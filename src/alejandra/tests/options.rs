@@ -0,0 +1,72 @@
+use alejandra::options::FormatOptions;
+use alejandra::options::IndentStyle;
+use alejandra::options::LineEnding;
+use pretty_assertions::assert_eq;
+
+fn format(before: &str, options: FormatOptions) -> String {
+    alejandra::format::in_memory_with(before.to_string(), &options).1
+}
+
+#[test]
+fn line_width_decides_what_fits_in_a_line() {
+    let before = "{ a = [ 1 2 3 ]; }\n";
+
+    let wide = FormatOptions::default().with_line_width(20);
+    assert_eq!(format(before, wide), "{ a = [1 2 3]; }\n");
+
+    let narrow = FormatOptions::default().with_line_width(10);
+    assert_eq!(
+        format(before, narrow),
+        "{\n  a = [\n    1\n    2\n    3\n  ];\n}\n"
+    );
+}
+
+#[test]
+fn indentation_follows_the_options() {
+    let before = "{\na = ''\nx\n'';\n}\n";
+
+    let spaces = FormatOptions::default().with_indent_width(4);
+    assert_eq!(format(before, spaces), "{\n    a = ''\n        x\n    '';\n}\n");
+
+    // Nix strips only spaces from indented strings.
+    let tabs = FormatOptions::default().with_indent_style(IndentStyle::Tabs);
+    assert_eq!(format(before, tabs), "{\n\ta = ''\n    x\n  '';\n}\n");
+}
+
+#[test]
+fn line_endings_follow_the_options() {
+    let before = "{\na = 1;\n}\n";
+
+    let crlf = FormatOptions::default().with_line_ending(LineEnding::Crlf);
+    assert_eq!(format(before, crlf), "{\r\n  a = 1;\r\n}\r\n");
+
+    let (_, after, source_map) = alejandra::format::in_memory_with_source_map(before.to_string(), &crlf);
+    assert_eq!(
+        source_map.map_offset(before.find('1').unwrap()),
+        after.find('1').unwrap()
+    );
+    assert_eq!(source_map.map_offset(before.len()), after.len());
+
+    let no_final_newline = FormatOptions::default().with_final_newline(false);
+    assert_eq!(format(before, no_final_newline), "{\n  a = 1;\n}");
+}
+
+#[test]
+fn line_endings_and_byte_order_marks_are_kept() {
+    let before = "\u{feff}{\r\na = 1;\r\n}\r\n";
+    assert_eq!(
+        format(before, FormatOptions::default()),
+        "\u{feff}{\r\n  a = 1;\r\n}\r\n"
+    );
+
+    let (_, after, source_map) =
+        alejandra::format::in_memory_with_source_map(before.to_string(), &FormatOptions::default());
+    assert_eq!(
+        source_map.map_offset(before.find('1').unwrap()),
+        after.find('1').unwrap()
+    );
+    assert_eq!(source_map.map_offset(before.len()), after.len());
+
+    let lf = FormatOptions::default().with_line_ending(LineEnding::Lf);
+    assert_eq!(format(before, lf), "\u{feff}{\n  a = 1;\n}\n");
+}
@@ -0,0 +1,72 @@
+use alejandra::format::Status;
+use alejandra::options::FormatOptions;
+use pretty_assertions::assert_eq;
+
+#[test]
+fn only_the_selected_binding_is_formatted() {
+    let before = "let\n  a   =   1;\n  b   =  [1   2];\nin   a\n".to_string();
+    let start = before.find('b').unwrap() as u32;
+    let range = rnix::TextRange::empty(start.into());
+
+    let (status, after, edited) =
+        alejandra::format::in_memory_range("test.nix".to_string(), before, range, &FormatOptions::default());
+
+    assert!(matches!(status, Status::Changed(true)));
+    assert_eq!(after, "let\n  a   =   1;\n  b = [1 2];\nin   a\n");
+    assert_eq!(&after[edited], "b = [1 2];");
+}
+
+#[test]
+fn line_endings_outside_of_the_range_are_kept() {
+    let before = "let\r\n  a   =   1;\r\n  b   =  [1   2];\nin   a\r\n".to_string();
+    let start = before.find('b').unwrap() as u32;
+    let range = rnix::TextRange::empty(start.into());
+
+    let (status, after, edited) =
+        alejandra::format::in_memory_range("test.nix".to_string(), before, range, &FormatOptions::default());
+
+    assert!(matches!(status, Status::Changed(true)));
+    assert_eq!(after, "let\r\n  a   =   1;\r\n  b = [1 2];\nin   a\r\n");
+    assert_eq!(&after[edited], "b = [1 2];");
+}
+
+#[test]
+fn nodes_kept_by_directives_are_not_formatted() {
+    let before = "[\n  # alejandra: skip\n  [ { a  =  1; } ]\n]\n".to_string();
+    let start = before.find("a  =").unwrap() as u32;
+    let end = before.find(';').unwrap() as u32 + 1;
+    let range = rnix::TextRange::new(start.into(), end.into());
+
+    let (status, after, _) = alejandra::format::in_memory_range(
+        "test.nix".to_string(),
+        before.clone(),
+        range,
+        &FormatOptions::default(),
+    );
+
+    assert!(matches!(status, Status::Changed(false)));
+    assert_eq!(after, before);
+}
+
+#[test]
+fn ranges_without_code_change_nothing() {
+    let before = "# header\n{\n  a = {b=1;};\n}\n".to_string();
+    let len = before.len() as u32;
+
+    for range in [
+        rnix::TextRange::new(0.into(), 8.into()),
+        rnix::TextRange::empty(len.into()),
+        rnix::TextRange::new((len + 10).into(), (len + 20).into()),
+    ] {
+        let (status, after, edited) = alejandra::format::in_memory_range(
+            "test.nix".to_string(),
+            before.clone(),
+            range,
+            &FormatOptions::default(),
+        );
+
+        assert!(matches!(status, Status::Changed(false)));
+        assert_eq!(after, before);
+        assert!(edited.is_empty());
+    }
+}
@@ -0,0 +1,18 @@
+use alejandra::options::FormatOptions;
+use pretty_assertions::assert_eq;
+
+#[test]
+fn offsets_follow_their_tokens() {
+    let before = "{\n    a   =   1;\n  b = [1   2];\n}\n".to_string();
+    let (_, after, source_map) =
+        alejandra::format::in_memory_with_source_map(before.clone(), &FormatOptions::default());
+
+    for token in ["a", "=", "b", "2"] {
+        let old = before.find(token).unwrap();
+        let new = after.find(token).unwrap();
+
+        assert_eq!(source_map.map_offset(old), new, "offset of `{token}`");
+    }
+
+    assert_eq!(source_map.map_offset(before.len()), after.len());
+}
@@ -0,0 +1,102 @@
+use alejandra::error::Error;
+use alejandra::format::Status;
+use alejandra::options::FormatOptions;
+use alejandra::options::IndentStyle;
+use pretty_assertions::assert_eq;
+
+#[test]
+fn formatting_the_cases_keeps_their_meaning() {
+    for entry in std::fs::read_dir("tests/cases").unwrap() {
+        let case = entry.unwrap().file_name().into_string().unwrap();
+
+        // `error` does not parse, and `string` indents a line with an EN SPACE,
+        // which Nix does not consider indentation but Alejandra does.
+        if case == "error" || case == "string" {
+            continue;
+        }
+
+        let path = format!("tests/cases/{case}/in.nix");
+        let before = std::fs::read_to_string(&path).unwrap();
+        let (_, after) = alejandra::format::in_memory(path.clone(), before.clone());
+
+        if let Err(error) = alejandra::format::check_equivalent(&before, &after) {
+            panic!("Test case `{case}` changed meaning: {error}");
+        }
+    }
+}
+
+#[test]
+fn redundant_parentheses_and_comments_are_ignored() {
+    let before = "# comment\n(a b) c";
+    let after = "a b c";
+
+    assert!(alejandra::format::check_equivalent(before, after).is_ok());
+}
+
+#[test]
+fn divergences_are_reported() {
+    let before = "a (b c)";
+    let after = "a b c";
+
+    match alejandra::format::check_equivalent(before, after) {
+        Err(Error::Verification(reason)) => assert_eq!(
+            reason,
+            "NODE_IDENT at 1:1 of the input became NODE_APPLY at 1:1 of the output".to_string()
+        ),
+        result => panic!("expected a verification error, got: {result:?}"),
+    }
+}
+
+/// Test cases that a second pass changes, or that cannot be formatted at all,
+/// with the reason for each.
+const UNSTABLE: &[(&str, &str)] = &[
+    ("error", "it does not parse"),
+    (
+        "comment",
+        "a `let` with comments inside of parentheses is laid out differently on the second pass",
+    ),
+];
+
+#[test]
+fn formatting_the_cases_twice_changes_nothing() {
+    for entry in std::fs::read_dir("tests/cases").unwrap() {
+        let case = entry.unwrap().file_name().into_string().unwrap();
+
+        if UNSTABLE.iter().any(|(unstable, _)| *unstable == case) {
+            continue;
+        }
+
+        let path = format!("tests/cases/{case}/in.nix");
+        let before = std::fs::read_to_string(&path).unwrap();
+
+        for line_width in (10..=120).step_by(10) {
+            for indent_style in [IndentStyle::Spaces, IndentStyle::Tabs] {
+                for indent_width in [2, 4] {
+                    let options = FormatOptions::default()
+                        .with_line_width(line_width)
+                        .with_indent_style(indent_style)
+                        .with_indent_width(indent_width);
+
+                    if let (Status::Error(error), _) =
+                        alejandra::format::check_idempotent(before.clone(), &options)
+                    {
+                        panic!(
+                            "Test case `{case}` is not idempotent at a line width of {line_width}, indented \
+                             by {indent_width} {indent_style:?}: {error}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn idempotent_output_is_returned() {
+    let before = "{\n    a   = 1;\n}\n".to_string();
+
+    let (status, after) = alejandra::format::check_idempotent(before, &FormatOptions::default());
+
+    assert!(matches!(status, Status::Changed(true)));
+    assert_eq!(after, "{\n  a = 1;\n}\n");
+}
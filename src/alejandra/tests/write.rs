@@ -0,0 +1,50 @@
+use pretty_assertions::assert_eq;
+
+fn scratch_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn in_place_writes_keep_a_backup() {
+    let dir = scratch_dir("in_place_writes_keep_a_backup");
+    let path = dir.join("a.nix");
+    std::fs::write(&path, "{a=1;}").unwrap();
+
+    let status = alejandra::format::in_fs_using(&path, true, Some(".orig"), alejandra::format::in_memory);
+
+    assert!(matches!(status, alejandra::format::Status::Changed(true)));
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "{ a = 1; }\n");
+    assert_eq!(std::fs::read_to_string(dir.join("a.nix.orig")).unwrap(), "{a=1;}");
+    // No temporary files are left behind.
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 2);
+}
+
+#[cfg(unix)]
+#[test]
+fn in_place_writes_follow_symbolic_links_and_keep_permissions() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = scratch_dir("in_place_writes_follow_symbolic_links_and_keep_permissions");
+    let target = dir.join("target.nix");
+    let link = dir.join("link.nix");
+    std::fs::write(&target, "before").unwrap();
+    std::fs::set_permissions(&target, std::fs::Permissions::from_mode(0o751)).unwrap();
+    std::os::unix::fs::symlink("target.nix", &link).unwrap();
+
+    alejandra::write::atomically(&link, "after", None).unwrap();
+
+    assert!(
+        std::fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink()
+    );
+    assert_eq!(std::fs::read_to_string(&target).unwrap(), "after");
+    assert_eq!(
+        std::fs::metadata(&target).unwrap().permissions().mode() & 0o777,
+        0o751
+    );
+}
@@ -1,13 +1,28 @@
+use std::io::IsTerminal;
 use std::io::Read;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
 
-use clap::value_parser;
 use clap::ArgAction;
 use clap::Parser;
+use clap::ValueEnum;
+use clap::builder::RangedU64ValueParser;
+use clap::value_parser;
 use futures::future::RemoteHandle;
 use futures::stream::FuturesUnordered;
 use futures::task::SpawnExt;
 
 use crate::ads::random_ad;
+use crate::config::Configs;
+use crate::filter::Filter;
+use crate::filter::Pattern;
+use crate::mode::IndentStyle;
+use crate::mode::LineEnding;
+use crate::mode::Mode;
+use crate::mode::Overrides;
+use crate::range::Region;
+use crate::report::ReportFormat;
 use crate::verbosity::Verbosity;
 
 /// The Uncompromising Nix Code Formatter.
@@ -21,43 +36,319 @@ use crate::verbosity::Verbosity;
         "  2, if --check was used and any file requires formatting.\n",
         "  0, otherwise.",
     ),
+    after_long_help = concat!(
+        "Settings are also read from an alejandra.toml file, or from the\n",
+        "[tool.alejandra] table of a pyproject.toml file, in the directory of\n",
+        "each file or in the nearest of its parents. They are named like the\n",
+        "command line options that take precedence over them: include,\n",
+        "exclude, ignore, threads, quiet, max-errors, line-width,\n",
+        "indent-width, indent-style, line-ending, final-newline, report,\n",
+        "report-file, verify, verify-idempotent, backup-suffix and\n",
+        "generated-markers, which replaces the default markers of generated\n",
+        "files. Patterns and paths are relative to the directory of the\n",
+        "configuration file. Settings that apply to the whole run, like\n",
+        "ignore, threads, quiet, max-errors, report and report-file, are\n",
+        "read from the configuration of the current directory instead.\n",
+        "\n",
+        "Alejandra will exit with status code:\n",
+        "  1, if any error occurs.\n",
+        "  2, if --check was used and any file requires formatting.\n",
+        "  0, otherwise.",
+    ),
     term_width = 80,
     version,
 )]
 struct CLIArgs {
     /// Files or directories, or a single "-" (or leave empty) to format stdin.
-    #[clap(multiple_values = true)]
-    include: Vec<String>,
+    /// With --staged, --changed-since or --untracked, they restrict the
+    /// selected files instead.
+    #[clap(value_name = "PATH", multiple_values = true, value_parser = value_parser!(PathBuf))]
+    include: Vec<PathBuf>,
+
+    /// Format the Nix files listed in this file, or in stdin if it is "-",
+    /// one per line. They are not walked even if they are directories, but
+    /// the exclude patterns and ignore files still apply to them.
+    #[clap(
+        long,
+        value_name = "FILE",
+        value_parser = value_parser!(PathBuf),
+        conflicts_with_all = &["include", "staged", "changed-since", "untracked"]
+    )]
+    files_from: Option<PathBuf>,
+
+    /// Paths in --files-from are separated by NUL characters instead of new
+    /// lines, like the output of `git ls-files -z` or `find -print0`.
+    #[clap(long = "null", short = '0', requires = "files-from")]
+    null: bool,
+
+    /// Format only the files that match this pattern. Can be given more
+    /// than once. See --exclude for the syntax of patterns. Without any
+    /// PATH, the current directory is searched instead of formatting stdin.
+    #[clap(
+        long = "include",
+        short,
+        value_name = "PATTERN",
+        value_parser = Pattern::parse,
+        multiple_occurrences = true
+    )]
+    include_patterns: Vec<Pattern>,
+
+    /// Files or directories to exclude from formatting, which are not
+    /// searched at all. Besides a path, this can be a glob like
+    /// `**/generated/*.nix` or `regex:REGEX`, matched against paths relative
+    /// to the current directory. A value with any of `*?[` is a glob unless
+    /// it starts with `path:`.
+    #[clap(long, short, value_name = "PATTERN", value_parser = Pattern::parse, multiple_occurrences = true)]
+    exclude: Vec<Pattern>,
 
-    /// Files or directories to exclude from formatting.
-    #[clap(long, short, multiple_occurrences = true)]
-    exclude: Vec<String>,
+    /// Do not skip what .gitignore, .ignore and .alejandraignore files
+    /// ignore when searching for files in directories. Ignore files apply to
+    /// their directory and below, and are also read from the parent
+    /// directories up to the root of the git repository, along with
+    /// .git/info/exclude and the global ignore file of git.
+    #[clap(long)]
+    no_ignore: bool,
+
+    /// Format the files that tools generated too, instead of skipping them.
+    /// They are recognized by the comments at their start, which contain
+    /// "@generated" or "DO NOT EDIT", or the generated-markers setting.
+    #[clap(long)]
+    format_generated: bool,
 
     /// Check if the input is already formatted and disable writing in-place
     /// the modified content.
     #[clap(long, short)]
     check: bool,
 
+    /// Print a unified diff of the changes each file requires, instead of
+    /// writing them in-place. Implies --check.
+    #[clap(long)]
+    diff: bool,
+
+    /// When to color the output of --diff. `auto` colors it if stdout is a
+    /// terminal and the NO_COLOR environment variable is not set.
+    #[clap(long, value_name = "WHEN", value_enum, default_value_t = Color::Auto)]
+    color: Color,
+
+    /// When to make the output reproducible: results sorted by path once
+    /// all files are formatted, and no random messages. `auto` does it if
+    /// the CI environment variable is set or stdout is not a terminal,
+    /// except with `--output-format ndjson`.
+    #[clap(long, value_name = "WHEN", value_enum, default_value_t = Deterministic::Auto)]
+    deterministic: Deterministic,
+
+    /// How to report the results. `json` prints an array with a record per
+    /// file once all of them are formatted, `ndjson` prints each record in
+    /// its own line as soon as the file is formatted, in no particular order
+    /// unless `--deterministic always`, which prints them sorted once all
+    /// files are formatted instead. Stdin requires --check in these formats,
+    /// since the formatted code is not printed.
+    #[clap(
+        long,
+        value_name = "FORMAT",
+        value_enum,
+        default_value_t = OutputFormat::Human,
+        conflicts_with_all = &["diff", "cursor-offset"],
+    )]
+    output_format: OutputFormat,
+
+    /// Also write a report of the files that require formatting and of the
+    /// errors found to --report-file, in this format.
+    #[clap(long, value_name = "FORMAT", value_enum, requires = "report-file")]
+    report: Option<ReportFormat>,
+
+    /// Where to write the report requested with --report.
+    #[clap(long, value_name = "PATH", requires = "report")]
+    report_file: Option<String>,
+
+    /// When writing in-place, keep the original of each file that changes
+    /// next to it, with this suffix appended to its name, like `.orig`.
+    #[clap(long, value_name = "SUFFIX")]
+    backup_suffix: Option<String>,
+
+    /// Format the Nix files staged in the git index, using their staged
+    /// content instead of the one in the work tree. When writing in-place,
+    /// the formatted content is staged, and written to the work tree too if
+    /// it has no unstaged changes.
+    #[clap(long, conflicts_with_all = &["changed-since", "untracked"])]
+    staged: bool,
+
+    /// Format the Nix files that changed in the git work tree since this
+    /// revision.
+    #[clap(long, value_name = "REV")]
+    changed_since: Option<String>,
+
+    /// Format the Nix files that are neither tracked nor ignored by git.
+    #[clap(long)]
+    untracked: bool,
+
     /// Number of formatting threads to spawn. Defaults to the number of
     /// physical CPUs.
     #[clap(long, short, value_parser = value_parser!(u8).range(1..))]
     threads: Option<u8>,
 
+    /// Format only the code enclosing this range of bytes, given as
+    /// START:END.
+    #[clap(long, value_name = "START:END", value_parser = Region::parse_bytes, conflicts_with = "lines")]
+    range: Option<Region>,
+
+    /// Format only the code enclosing these lines, given as FIRST-LAST,
+    /// starting at 1.
+    #[clap(long, value_name = "FIRST-LAST", value_parser = Region::parse_lines)]
+    lines: Option<Region>,
+
+    /// When formatting stdin, translate this byte offset of the input to
+    /// the equivalent offset of the output, and print it as
+    /// `{ "Cursor": N }` in the first line of the output.
+    #[clap(long, value_name = "N", conflicts_with_all = &["range", "lines"])]
+    cursor_offset: Option<usize>,
+
+    /// When formatting stdin, report it as the file at this path, and apply
+    /// the exclude patterns and the configuration file that apply to it.
+    /// If that file is excluded, stdin is printed unchanged.
+    #[clap(long, value_name = "PATH", value_parser = value_parser!(PathBuf))]
+    stdin_filepath: Option<PathBuf>,
+
+    /// Make sure that the formatted code means the same as the original,
+    /// and leave the original untouched otherwise.
+    #[clap(long)]
+    verify: bool,
+
+    /// Make sure that formatting the formatted code again changes nothing,
+    /// and leave the original untouched otherwise.
+    #[clap(long, conflicts_with_all = &["range", "lines", "cursor-offset"])]
+    verify_idempotent: bool,
+
+    /// Maximum number of characters in a line. Code that does not fit is
+    /// laid out across several lines. Defaults to 120.
+    #[clap(long, value_name = "N", value_parser = value_parser!(u16).range(1..))]
+    line_width: Option<u16>,
+
+    /// Number of spaces per level of indentation, or columns per tab with
+    /// --indent-style tabs. Defaults to 2.
+    #[clap(long, value_name = "N", value_parser = value_parser!(u8).range(1..))]
+    indent_width: Option<u8>,
+
+    /// Indent with spaces or with tabs. Defaults to spaces.
+    #[clap(long, value_name = "STYLE", value_enum)]
+    indent_style: Option<IndentStyle>,
+
+    /// End lines with "\n" (lf), with "\r\n" (crlf), or like most lines of
+    /// each file (auto). Defaults to auto.
+    #[clap(long, value_name = "ENDING", value_enum)]
+    line_ending: Option<LineEnding>,
+
+    /// Do not end the formatted code with a line ending.
+    #[clap(long)]
+    no_final_newline: bool,
+
+    /// Maximum number of syntax errors to report per file. Defaults to 10.
+    #[clap(long, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    max_errors: Option<usize>,
+
     /// Use once to hide informational messages,
     /// twice to hide error messages.
     #[clap(long, short, action = ArgAction::Count)]
     quiet: u8,
+
+    /// Print the settings in effect in the current directory, from its
+    /// configuration file and the command line, and exit.
+    #[clap(long)]
+    print_config: bool,
+}
+
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+enum Color {
+    Auto,
+    Always,
+    Never,
+}
+
+impl Color {
+    fn enabled(self) -> bool {
+        match self {
+            Color::Auto => std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none(),
+            Color::Always => true,
+            Color::Never => false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+enum Deterministic {
+    Auto,
+    Always,
+    Never,
+}
+
+impl Deterministic {
+    fn enabled(self, output_format: OutputFormat) -> bool {
+        match self {
+            // NDJSON is meant to be read as each file is formatted,
+            // and is rarely printed to a terminal.
+            Deterministic::Auto if matches!(output_format, OutputFormat::Ndjson) => false,
+            Deterministic::Auto => std::env::var_os("CI").is_some() || !std::io::stdout().is_terminal(),
+            Deterministic::Always => true,
+            Deterministic::Never => false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+enum OutputFormat {
+    Human,
+    Json,
+    Ndjson,
+}
+
+/// How to display the formatted code.
+#[derive(Clone, Copy)]
+enum Output {
+    /// The formatted code itself when formatting stdin,
+    /// and the path of each changed file otherwise.
+    Code,
+    /// A unified diff of the changes, colored if true.
+    Diff(bool),
+    /// A JSON array with a record per file, once all of them are formatted.
+    Json,
+    /// A JSON record per line, as soon as each file is formatted.
+    Ndjson,
+}
+
+impl Output {
+    fn is_machine_readable(self) -> bool {
+        matches!(self, Output::Json | Output::Ndjson)
+    }
 }
 
 #[derive(Clone)]
-struct FormattedPath {
-    pub path:   String,
-    pub status: alejandra::format::Status,
+pub(crate) struct FormattedPath {
+    pub path:          PathBuf,
+    pub status:        alejandra::format::Status,
+    /// How long it took to format the file.
+    pub duration:      std::time::Duration,
+    /// Lines of the file that require formatting, if requested,
+    /// as ranges from the first to the last line.
+    pub changed_lines: Vec<(usize, usize)>,
+    /// Whether the file was left alone because a tool generated it.
+    pub skipped:       bool,
+    /// How many syntax errors were left out of `status`,
+    /// see [`Mode::limited`].
+    pub omitted:       usize,
 }
 
-fn format_stdin(verbosity: Verbosity) -> FormattedPath {
+/// Formats stdin as the file at `path`,
+/// or prints it unchanged if that file is `excluded` or generated.
+fn format_stdin(
+    path: PathBuf,
+    excluded: bool,
+    mode: Mode,
+    output: Output,
+    track_changes: bool,
+    cursor_offset: Option<usize>,
+    verbosity: Verbosity,
+) -> FormattedPath {
     let mut before = String::new();
-    let path = "<anonymous file on stdin>".to_string();
 
     if verbosity.allows_info() {
         eprintln!("Formatting stdin.");
@@ -69,18 +360,84 @@ fn format_stdin(verbosity: Verbosity) -> FormattedPath {
         .read_to_string(&mut before)
         .expect("Unable to read stdin.");
 
-    let (status, data) = alejandra::format::in_memory(path.clone(), before.clone());
+    let start = std::time::Instant::now();
+    let skipped = !excluded && crate::generated::is_generated(&before, &mode.generated_markers);
+    let (status, data, cursor_offset) = match cursor_offset {
+        _ if excluded || skipped => (
+            alejandra::format::Status::Changed(false),
+            before.clone(),
+            cursor_offset,
+        ),
+        Some(cursor_offset) => {
+            let (status, data, source_map) =
+                alejandra::format::in_memory_with_source_map(before.clone(), &mode.options);
+            let (status, data) = mode.verified(before.clone(), status, data);
 
-    print!("{data}");
+            let cursor_offset = match status {
+                alejandra::format::Status::Changed(true) => source_map.map_offset(cursor_offset),
+                _ => cursor_offset,
+            };
+
+            (status, data, Some(cursor_offset))
+        }
+        None => {
+            let (status, data) = mode.in_memory(path.to_string_lossy().into_owned(), before.clone());
+            (status, data, None)
+        }
+    };
+
+    let duration = start.elapsed();
+    let (status, omitted) = mode.limited(status);
+    let formatted_path = FormattedPath {
+        path: path.clone(),
+        status,
+        duration,
+        changed_lines: if track_changes {
+            crate::diff::changed_lines(&before, &data)
+        } else {
+            Vec::new()
+        },
+        skipped,
+        omitted,
+    };
+
+    match output {
+        Output::Code if mode.in_place => {
+            if let Some(cursor_offset) = cursor_offset {
+                println!("{{ \"Cursor\": {cursor_offset} }}");
+            }
+            print!("{data}");
+        }
+        Output::Code => {
+            if data != before && verbosity.allows_info() {
+                println!("Requires formatting: {}", path.display());
+            }
+        }
+        Output::Diff(color) => print!(
+            "{}",
+            crate::diff::unified(&path.display().to_string(), &before, &data, color)
+        ),
+        // Records are printed by the caller.
+        Output::Json | Output::Ndjson => {}
+    }
 
-    FormattedPath { path, status }
+    formatted_path
 }
 
+/// Formats `paths` in a pool of `threads`,
+/// each configured like `mode` and its nearest configuration file.
+///
+/// Results are printed as soon as each file is formatted, unless
+/// `deterministic`, in which case they are printed and returned sorted by
+/// path once all of them are, and records are left for the caller to print.
 fn format_paths(
-    paths: Vec<String>,
-    in_place: bool,
+    paths: Vec<PathBuf>,
+    mode: Mode,
+    output: Output,
+    track_changes: bool,
     verbosity: Verbosity,
     threads: usize,
+    deterministic: bool,
 ) -> Vec<FormattedPath> {
     let paths_len = paths.len();
 
@@ -98,72 +455,483 @@ fn format_paths(
         .create()
         .expect("Unable to instantiate a new thread pool.");
 
-    let futures: FuturesUnordered<RemoteHandle<FormattedPath>> = paths
+    let futures: FuturesUnordered<RemoteHandle<(FormattedPath, String)>> = paths
         .into_iter()
         .map(|path| {
+            let mode = mode.configured_for(&path);
+
             pool.spawn_with_handle(async move {
-                let status = alejandra::format::in_fs(path.clone(), in_place);
+                let start = std::time::Instant::now();
+                let mut changed_lines = Vec::new();
+                let mut skipped = false;
+                // What to print to stdout about the file.
+                let mut printed = String::new();
+                let formatter = |path: String, before: String| {
+                    if crate::generated::is_generated(&before, &mode.generated_markers) {
+                        skipped = true;
+                        return (alejandra::format::Status::Changed(false), before);
+                    }
 
+                    let (status, after) = mode.in_memory(path.clone(), before.clone());
+
+                    if let Output::Diff(color) = output {
+                        printed.push_str(&crate::diff::unified(&path, &before, &after, color));
+                    }
+                    if track_changes {
+                        changed_lines = crate::diff::changed_lines(&before, &after);
+                    }
+
+                    (status, after)
+                };
+                // A bug while formatting one file must not stop the others.
+                let status = crate::panic::catch(|| {
+                    if let Some(index) = &mode.staged {
+                        crate::git::format_staged(
+                            &path,
+                            mode.in_place,
+                            mode.backup_suffix.as_deref(),
+                            index,
+                            formatter,
+                        )
+                    } else {
+                        alejandra::format::in_fs_using(
+                            &path,
+                            mode.in_place,
+                            mode.backup_suffix.as_deref(),
+                            formatter,
+                        )
+                    }
+                })
+                .unwrap_or_else(alejandra::format::Status::InternalError);
+
+                if skipped && verbosity.allows_info() && matches!(output, Output::Code) {
+                    printed.push_str(&format!("Skipped generated file: {}\n", path.display()));
+                }
                 if let alejandra::format::Status::Changed(changed) = status {
-                    if changed && verbosity.allows_info() {
-                        println!(
-                            "{}: {path}",
-                            if in_place {
+                    if changed && verbosity.allows_info() && matches!(output, Output::Code) {
+                        printed.push_str(&format!(
+                            "{}: {}\n",
+                            if mode.in_place {
                                 "Formatted"
                             } else {
                                 "Requires formatting"
                             },
-                        );
+                            path.display(),
+                        ));
                     }
                 }
 
-                FormattedPath {
+                let duration = start.elapsed();
+                let (status, omitted) = mode.limited(status);
+                let formatted_path = FormattedPath {
                     path: path.clone(),
                     status,
-                }
+                    duration,
+                    changed_lines,
+                    skipped,
+                    omitted,
+                };
+
+                (formatted_path, printed)
             })
             .expect("Unable to spawn formatting task.")
         })
         .collect();
 
-    futures::executor::block_on_stream(futures).collect()
+    let results = futures::executor::block_on_stream(futures);
+
+    if deterministic {
+        let mut results: Vec<(FormattedPath, String)> = results.collect();
+        results.sort_by(|(a, _), (b, _)| a.path.cmp(&b.path));
+
+        results
+            .into_iter()
+            .map(|(formatted_path, printed)| {
+                print!("{printed}");
+                formatted_path
+            })
+            .collect()
+    } else {
+        results
+            .map(|(formatted_path, printed)| {
+                print!("{printed}");
+                if let Output::Ndjson = output {
+                    println!("{}", crate::json::record(&formatted_path));
+                }
+                formatted_path
+            })
+            .collect()
+    }
+}
+
+/// The result for an entry that was found, but could not be read,
+/// printed like those of [`format_paths`].
+fn unreadable(path: PathBuf, error: std::io::Error, output: Output, deterministic: bool) -> FormattedPath {
+    let formatted_path = FormattedPath {
+        path,
+        status: alejandra::format::Status::from(error),
+        duration: std::time::Duration::ZERO,
+        changed_lines: Vec::new(),
+        skipped: false,
+        omitted: 0,
+    };
+    if let (Output::Ndjson, false) = (output, deterministic) {
+        println!("{}", crate::json::record(&formatted_path));
+    }
+
+    formatted_path
 }
 
 pub fn main() -> std::io::Result<()> {
     let args = CLIArgs::parse();
 
-    let in_place = !args.check;
+    let configs = Arc::new(Configs::default());
+    let config = match &args.stdin_filepath {
+        // Stdin is configured like the file it stands for.
+        Some(path) => {
+            let path = crate::filter::absolute(path);
+            configs.nearest(path.parent().unwrap_or(&path))
+        }
+        None => configs.nearest(Path::new(".")),
+    };
+    let config = match config {
+        Ok(config) => config.unwrap_or_default(),
+        Err(error) => {
+            eprintln!("{error}");
+            std::process::exit(1);
+        }
+    };
 
-    let include: Vec<&str> = args
-        .include
-        .iter()
-        .map(String::as_str)
-        .collect::<Vec<&str>>();
+    let in_place = !args.check && !args.diff;
+    let deterministic = args.deterministic.enabled(args.output_format);
 
     let threads = args
         .threads
+        .or(config.threads)
         .map_or_else(num_cpus::get_physical, Into::<usize>::into);
 
-    let verbosity = match args.quiet {
+    let quiet = if args.quiet > 0 {
+        args.quiet
+    } else {
+        config.quiet.unwrap_or(0)
+    };
+    let max_errors = args.max_errors.or(config.max_errors).unwrap_or(10);
+    let honor_ignores = !args.no_ignore && config.ignore.unwrap_or(true);
+    let (report, report_file) = match args.report {
+        Some(report) => (Some(report), args.report_file.clone()),
+        None => (config.report, config.report_file.clone()),
+    };
+
+    let selection = crate::git::Selection {
+        staged:        args.staged,
+        changed_since: args.changed_since.clone(),
+        untracked:     args.untracked,
+    };
+
+    // All the selected files are named relative to the top level
+    // of the repository.
+    let toplevel = if selection.is_empty() {
+        None
+    } else {
+        match crate::git::toplevel() {
+            Ok(toplevel) => Some(toplevel),
+            Err(error) => {
+                eprintln!("{error}");
+                std::process::exit(1);
+            }
+        }
+    };
+
+    let index = match &toplevel {
+        Some(toplevel) if args.staged => match crate::git::Index::new(toplevel.clone()) {
+            Ok(index) => Some(Arc::new(index)),
+            Err(error) => {
+                eprintln!("{error}");
+                std::process::exit(1);
+            }
+        },
+        _ => None,
+    };
+
+    // Formatting settings are configured again for each file,
+    // see `format_paths`.
+    let mode = Mode {
+        overrides: Overrides {
+            line_width:        args.line_width.map(usize::from),
+            indent_width:      args.indent_width.map(usize::from),
+            indent_style:      args.indent_style,
+            line_ending:       args.line_ending,
+            no_final_newline:  args.no_final_newline,
+            verify:            args.verify,
+            verify_idempotent: args.verify_idempotent,
+            backup_suffix:     args.backup_suffix.clone(),
+            format_generated:  args.format_generated,
+        },
+        region: args.range.or(args.lines),
+        staged: index.clone(),
+        in_place,
+        max_errors,
+        configs: configs.clone(),
+        ..Mode::default()
+    }
+    .configured(&config);
+
+    if args.print_config {
+        let sources = |patterns: &[Pattern]| -> Vec<String> {
+            patterns
+                .iter()
+                .map(|pattern| pattern.source.clone())
+                .collect()
+        };
+
+        let mut settings = serde_json::Map::new();
+        settings.insert(
+            "include".to_string(),
+            serde_json::json!(if args.include_patterns.is_empty() {
+                sources(&config.patterns.include)
+            } else {
+                sources(&args.include_patterns)
+            }),
+        );
+        settings.insert(
+            "exclude".to_string(),
+            serde_json::json!([sources(&config.patterns.exclude), sources(&args.exclude)].concat()),
+        );
+        settings.insert("ignore".to_string(), serde_json::json!(honor_ignores));
+        settings.insert("threads".to_string(), serde_json::json!(threads));
+        settings.insert("quiet".to_string(), serde_json::json!(quiet));
+        settings.insert("max-errors".to_string(), serde_json::json!(max_errors));
+        let options = &mode.options;
+        settings.insert("line-width".to_string(), serde_json::json!(options.line_width));
+        settings.insert("indent-width".to_string(), serde_json::json!(options.indent_width));
+        settings.insert(
+            "indent-style".to_string(),
+            serde_json::json!(
+                IndentStyle::from(options.indent_style)
+                    .to_possible_value()
+                    .unwrap()
+                    .get_name()
+            ),
+        );
+        settings.insert(
+            "line-ending".to_string(),
+            serde_json::json!(
+                LineEnding::from(options.line_ending)
+                    .to_possible_value()
+                    .unwrap()
+                    .get_name()
+            ),
+        );
+        settings.insert("final-newline".to_string(), serde_json::json!(options.final_newline));
+        if let (Some(report), Some(report_file)) = (report, &report_file) {
+            settings.insert(
+                "report".to_string(),
+                serde_json::json!(report.to_possible_value().unwrap().get_name()),
+            );
+            settings.insert("report-file".to_string(), serde_json::json!(report_file));
+        }
+        if let Some(backup_suffix) = &mode.backup_suffix {
+            settings.insert("backup-suffix".to_string(), serde_json::json!(backup_suffix));
+        }
+        settings.insert(
+            "generated-markers".to_string(),
+            serde_json::json!(mode.generated_markers),
+        );
+        settings.insert("verify".to_string(), serde_json::json!(mode.verify));
+        settings.insert(
+            "verify-idempotent".to_string(),
+            serde_json::json!(mode.verify_idempotent),
+        );
+
+        if config.path.as_os_str().is_empty() {
+            println!("# No configuration file found.");
+        } else {
+            println!("# From {}", config.path.display());
+        }
+        print!("{}", toml::to_string(&settings).unwrap());
+        std::process::exit(0);
+    }
+
+    let output = match (args.diff, args.output_format) {
+        (true, _) => Output::Diff(args.color.enabled()),
+        (false, OutputFormat::Human) => Output::Code,
+        (false, OutputFormat::Json) => Output::Json,
+        (false, OutputFormat::Ndjson) => Output::Ndjson,
+    };
+
+    let verbosity = match quiet {
+        // Keep stdout machine-readable.
+        0 if output.is_machine_readable() => Verbosity::NoInfo,
         0 => Verbosity::Everything,
         1 => Verbosity::NoInfo,
         _ => Verbosity::NoErrors,
     };
 
-    let formatted_paths = match &include[..] {
-        &[] | &["-"] => {
-            vec![crate::cli::format_stdin(verbosity)]
+    let track_changes = report.is_some();
+
+    let filter = Filter::new(
+        args.include_patterns.clone(),
+        args.exclude.clone(),
+        configs.clone(),
+    );
+
+    // Include patterns alone search the current directory.
+    let searches_current_dir =
+        args.include.is_empty() && !args.include_patterns.is_empty() && args.stdin_filepath.is_none();
+    let formats_stdin = selection.is_empty()
+        && args.files_from.is_none()
+        && !searches_current_dir
+        && (args.include.is_empty() || args.include == [Path::new("-")]);
+    if !formats_stdin {
+        for (flag, given) in [
+            ("--cursor-offset", args.cursor_offset.is_some()),
+            ("--stdin-filepath", args.stdin_filepath.is_some()),
+        ] {
+            if given {
+                eprintln!("{flag} can only be used when formatting stdin.");
+                std::process::exit(1);
+            }
         }
-        include => {
-            let paths = crate::find::nix_files(include, &args.exclude);
+    }
+    if formats_stdin && in_place && output.is_machine_readable() {
+        eprintln!("--output-format json and ndjson require --check when formatting stdin.");
+        std::process::exit(1);
+    }
+
+    let mut formatted_paths = match (&toplevel, &args.include[..]) {
+        (Some(toplevel), pathspecs) => {
+            let paths = match selection.nix_files(toplevel, pathspecs) {
+                Ok(paths) => paths,
+                Err(error) => {
+                    eprintln!("{error}");
+                    std::process::exit(1);
+                }
+            };
+            let mut unreadable = Vec::new();
+            let paths = paths
+                .into_iter()
+                .filter(|path| crate::find::allowed(filter.allows(path), &mut unreadable))
+                .collect();
 
-            crate::cli::format_paths(paths, in_place, verbosity, threads)
+            let mut formatted_paths = crate::cli::format_paths(
+                paths,
+                mode,
+                output,
+                track_changes,
+                verbosity,
+                threads,
+                deterministic,
+            );
+
+            if let Some(index) = index {
+                if let Err(error) = index.write() {
+                    eprintln!("Unable to stage the formatted files: {error}");
+                    std::process::exit(1);
+                }
+            }
+
+            for (path, error) in unreadable {
+                formatted_paths.push(crate::cli::unreadable(path, error, output, deterministic));
+            }
+            formatted_paths
+        }
+        _ if formats_stdin => {
+            let (path, excluded) = match &args.stdin_filepath {
+                Some(path) => match filter.allows(path) {
+                    Ok(allowed) => (path.clone(), !allowed),
+                    Err(error) => {
+                        eprintln!("{error}");
+                        std::process::exit(1);
+                    }
+                },
+                None => (PathBuf::from("<anonymous file on stdin>"), false),
+            };
+
+            let formatted_path = crate::cli::format_stdin(
+                path,
+                excluded,
+                mode,
+                output,
+                track_changes,
+                args.cursor_offset,
+                verbosity,
+            );
+            if let (Output::Ndjson, false) = (output, deterministic) {
+                println!("{}", crate::json::record(&formatted_path));
+            }
+            vec![formatted_path]
+        }
+        (None, include) => {
+            let (paths, unreadable) = match &args.files_from {
+                Some(files_from) => {
+                    let separator = if args.null { b'\0' } else { b'\n' };
+                    let listed = match crate::find::read_list(files_from, separator) {
+                        Ok(listed) => listed,
+                        Err(error) => {
+                            eprintln!(
+                                "Unable to read the list of files from {}: {error}",
+                                files_from.display()
+                            );
+                            std::process::exit(1);
+                        }
+                    };
+
+                    crate::find::listed_nix_files(listed, &filter, honor_ignores)
+                }
+                None if searches_current_dir => {
+                    crate::find::nix_files(&[PathBuf::from(".")], &filter, honor_ignores)
+                }
+                None => crate::find::nix_files(include, &filter, honor_ignores),
+            };
+
+            let mut formatted_paths = crate::cli::format_paths(
+                paths,
+                mode,
+                output,
+                track_changes,
+                verbosity,
+                threads,
+                deterministic,
+            );
+            for (path, error) in unreadable {
+                formatted_paths.push(crate::cli::unreadable(path, error, output, deterministic));
+            }
+            formatted_paths
         }
     };
 
+    if deterministic {
+        formatted_paths.sort_by(|a, b| a.path.cmp(&b.path));
+    }
+
+    match output {
+        Output::Json => {
+            let records: Vec<serde_json::Value> = formatted_paths.iter().map(crate::json::record).collect();
+            println!("{}", serde_json::Value::Array(records));
+        }
+        Output::Ndjson if deterministic => {
+            for formatted_path in &formatted_paths {
+                println!("{}", crate::json::record(formatted_path));
+            }
+        }
+        _ => {}
+    }
+
+    if let (Some(report), Some(report_file)) = (report, &report_file) {
+        if let Err(error) = crate::report::write(report, report_file, &formatted_paths) {
+            eprintln!("Unable to write the report to {report_file}: {error}");
+            std::process::exit(1);
+        }
+    }
+
     let errors = formatted_paths
         .iter()
-        .filter(|formatted_path| matches!(formatted_path.status, alejandra::format::Status::Error(_)))
+        .filter(|formatted_path| {
+            matches!(
+                formatted_path.status,
+                alejandra::format::Status::Error(_) | alejandra::format::Status::InternalError(_)
+            )
+        })
         .count();
 
     if errors > 0 {
@@ -174,8 +942,27 @@ pub fn main() -> std::io::Result<()> {
                 if errors == 1 { "" } else { "s" }
             );
             for formatted_path in formatted_paths {
-                if let alejandra::format::Status::Error(error) = formatted_path.status {
-                    eprintln!("- {}: {error}", formatted_path.path);
+                match formatted_path.status {
+                    alejandra::format::Status::Error(alejandra::error::Error::Parse(errors)) => {
+                        for error in errors {
+                            eprintln!("- {}: {error}", formatted_path.path.display());
+                        }
+                        if formatted_path.omitted > 0 {
+                            let omitted = formatted_path.omitted;
+                            eprintln!(
+                                "- {}: ... and {omitted} more syntax error{}",
+                                formatted_path.path.display(),
+                                if omitted == 1 { "" } else { "s" }
+                            );
+                        }
+                    }
+                    alejandra::format::Status::Error(error) => {
+                        eprintln!("- {}: {error}", formatted_path.path.display());
+                    }
+                    alejandra::format::Status::InternalError(error) => {
+                        eprintln!("- {}: internal error: {error}", formatted_path.path.display());
+                    }
+                    _ => {}
                 }
             }
         }
@@ -183,6 +970,20 @@ pub fn main() -> std::io::Result<()> {
         std::process::exit(1);
     }
 
+    let skipped = formatted_paths
+        .iter()
+        .filter(|formatted_path| formatted_path.skipped)
+        .count();
+
+    if skipped > 0 && verbosity.allows_info() {
+        eprintln!();
+        eprintln!(
+            "Skipped {skipped} generated file{}, use --format-generated to format {}.",
+            if skipped == 1 { "" } else { "s" },
+            if skipped == 1 { "it" } else { "them" },
+        );
+    }
+
     let changed = formatted_paths
         .iter()
         .filter(|formatted_path| match formatted_path.status {
@@ -206,7 +1007,7 @@ pub fn main() -> std::io::Result<()> {
                 }
             );
 
-            if in_place {
+            if in_place && !deterministic {
                 eprintln!();
                 eprint!("{}", random_ad());
             }
@@ -218,8 +1019,10 @@ pub fn main() -> std::io::Result<()> {
     if verbosity.allows_info() {
         eprintln!();
         eprintln!("Congratulations! Your code complies with the Alejandra style.");
-        eprintln!();
-        eprint!("{}", random_ad());
+        if !deterministic {
+            eprintln!();
+            eprint!("{}", random_ad());
+        }
     }
 
     std::process::exit(0);
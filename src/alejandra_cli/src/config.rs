@@ -0,0 +1,327 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use alejandra::options::FormatOptions;
+use clap::ValueEnum;
+use toml::Table;
+use toml::Value;
+
+use crate::filter::Pattern;
+use crate::filter::Patterns;
+use crate::mode::IndentStyle;
+use crate::mode::LineEnding;
+use crate::report::ReportFormat;
+
+/// The configuration file of a project.
+pub(crate) const CONFIG_FILE: &str = "alejandra.toml";
+
+/// Files shared with other tools,
+/// that hold the configuration under `[tool.alejandra]`.
+const SHARED_CONFIG_FILES: [&str; 1] = ["pyproject.toml"];
+
+/// The settings of a configuration file,
+/// which apply to the directory that contains it and below.
+#[derive(Debug, Default)]
+pub(crate) struct Config {
+    /// Where the settings were read from.
+    pub path:              PathBuf,
+    /// Patterns relative to the directory of the file.
+    pub patterns:          Patterns,
+    pub ignore:            Option<bool>,
+    pub threads:           Option<u8>,
+    pub quiet:             Option<u8>,
+    pub max_errors:        Option<usize>,
+    pub line_width:        Option<usize>,
+    pub indent_width:      Option<usize>,
+    pub indent_style:      Option<IndentStyle>,
+    pub line_ending:       Option<LineEnding>,
+    pub final_newline:     Option<bool>,
+    pub report:            Option<ReportFormat>,
+    /// Resolved against the directory of the file.
+    pub report_file:       Option<String>,
+    pub verify:            Option<bool>,
+    pub verify_idempotent: Option<bool>,
+    pub backup_suffix:     Option<String>,
+    pub generated_markers: Option<Vec<String>>,
+}
+
+/// The formatting options for the file at `path`:
+/// `options`, with the settings of the configuration file nearest to it.
+///
+/// For editors, which know the file but have no command line,
+/// and whose own settings are overridden by those of the project.
+pub fn format_options_for(path: &Path, options: FormatOptions) -> Result<FormatOptions, String> {
+    let path = crate::filter::absolute(path);
+
+    match Configs::default().nearest(path.parent().unwrap_or(&path)) {
+        Ok(Some(config)) => Ok(config.format_options(options)),
+        Ok(None) => Ok(options),
+        Err(error) => Err(error.to_string()),
+    }
+}
+
+impl Config {
+    /// `options`, with the formatting settings of this configuration.
+    pub(crate) fn format_options(&self, mut options: FormatOptions) -> FormatOptions {
+        if let Some(line_width) = self.line_width {
+            options = options.with_line_width(line_width);
+        }
+        if let Some(indent_width) = self.indent_width {
+            options = options.with_indent_width(indent_width);
+        }
+        if let Some(indent_style) = self.indent_style {
+            options = options.with_indent_style(indent_style.into());
+        }
+        if let Some(line_ending) = self.line_ending {
+            options = options.with_line_ending(line_ending.into());
+        }
+        if let Some(final_newline) = self.final_newline {
+            options = options.with_final_newline(final_newline);
+        }
+
+        options
+    }
+
+    /// Reads the configuration in `path`,
+    /// if it is not a shared file without it.
+    ///
+    /// Shared files that are not valid TOML are left to the other tools.
+    fn read(path: &Path, shared: bool) -> Result<Option<Config>, String> {
+        let text = std::fs::read_to_string(path).map_err(|error| error.to_string())?;
+        let table = match text.parse::<Table>() {
+            Ok(table) => table,
+            Err(_) if shared => return Ok(None),
+            Err(error) => return Err(error.to_string()),
+        };
+
+        if shared {
+            return match table.get("tool").and_then(|tool| tool.get("alejandra")) {
+                Some(Value::Table(table)) => Config::from_table(path, table).map(Some),
+                Some(_) => Err("`tool.alejandra` is not a table".to_string()),
+                None => Ok(None),
+            };
+        }
+
+        Config::from_table(path, &table).map(Some)
+    }
+
+    fn from_table(path: &Path, table: &Table) -> Result<Config, String> {
+        let base = path.parent().unwrap_or(Path::new("."));
+        let mut config = Config {
+            path: path.to_path_buf(),
+            patterns: Patterns {
+                base: base.to_path_buf(),
+                ..Patterns::default()
+            },
+            ..Config::default()
+        };
+
+        for (key, value) in table {
+            let invalid = |expected: &str| format!("`{key}` must be {expected}");
+
+            match key.as_str() {
+                "include" | "exclude" => {
+                    let patterns = value
+                        .as_array()
+                        .and_then(|values| values.iter().map(Value::as_str).collect::<Option<Vec<_>>>())
+                        .ok_or_else(|| invalid("an array of strings"))?
+                        .into_iter()
+                        .map(|value| {
+                            Pattern::parse_in(base, value)
+                                .map_err(|error| format!("invalid pattern in `{key}`: {error}"))
+                        })
+                        .collect::<Result<_, _>>()?;
+
+                    if key == "include" {
+                        config.patterns.include = patterns;
+                    } else {
+                        config.patterns.exclude = patterns;
+                    }
+                }
+                "ignore" => config.ignore = Some(value.as_bool().ok_or_else(|| invalid("a boolean"))?),
+                "threads" => {
+                    config.threads = Some(
+                        value
+                            .as_integer()
+                            .and_then(|threads| u8::try_from(threads).ok())
+                            .filter(|threads| *threads > 0)
+                            .ok_or_else(|| invalid("an integer from 1 to 255"))?,
+                    )
+                }
+                "quiet" => {
+                    config.quiet = Some(
+                        value
+                            .as_integer()
+                            .and_then(|quiet| u8::try_from(quiet).ok())
+                            .filter(|quiet| *quiet <= 2)
+                            .ok_or_else(|| invalid("0, 1 or 2"))?,
+                    )
+                }
+                "max-errors" => {
+                    config.max_errors = Some(
+                        value
+                            .as_integer()
+                            .and_then(|max_errors| usize::try_from(max_errors).ok())
+                            .filter(|max_errors| *max_errors > 0)
+                            .ok_or_else(|| invalid("a positive integer"))?,
+                    )
+                }
+                "line-width" => {
+                    config.line_width = Some(
+                        value
+                            .as_integer()
+                            .and_then(|line_width| usize::try_from(line_width).ok())
+                            .filter(|line_width| *line_width > 0)
+                            .ok_or_else(|| invalid("a positive integer"))?,
+                    )
+                }
+                "indent-width" => {
+                    config.indent_width = Some(
+                        value
+                            .as_integer()
+                            .and_then(|indent_width| usize::try_from(indent_width).ok())
+                            .filter(|indent_width| *indent_width > 0)
+                            .ok_or_else(|| invalid("a positive integer"))?,
+                    )
+                }
+                "indent-style" => {
+                    config.indent_style = Some(
+                        value
+                            .as_str()
+                            .and_then(|indent_style| IndentStyle::from_str(indent_style, false).ok())
+                            .ok_or_else(|| invalid("one of \"spaces\" or \"tabs\""))?,
+                    )
+                }
+                "line-ending" => {
+                    config.line_ending = Some(
+                        value
+                            .as_str()
+                            .and_then(|line_ending| LineEnding::from_str(line_ending, false).ok())
+                            .ok_or_else(|| invalid("one of \"auto\", \"lf\" or \"crlf\""))?,
+                    )
+                }
+                "final-newline" => {
+                    config.final_newline = Some(value.as_bool().ok_or_else(|| invalid("a boolean"))?)
+                }
+                "report" => {
+                    config.report = Some(
+                        value
+                            .as_str()
+                            .and_then(|report| ReportFormat::from_str(report, false).ok())
+                            .ok_or_else(|| invalid("one of \"sarif\", \"checkstyle\" or \"junit\""))?,
+                    )
+                }
+                "report-file" => {
+                    let report_file = value.as_str().ok_or_else(|| invalid("a string"))?;
+                    config.report_file = Some(base.join(report_file).to_string_lossy().into_owned());
+                }
+                "verify" => config.verify = Some(value.as_bool().ok_or_else(|| invalid("a boolean"))?),
+                "verify-idempotent" => {
+                    config.verify_idempotent = Some(value.as_bool().ok_or_else(|| invalid("a boolean"))?)
+                }
+                "backup-suffix" => {
+                    config.backup_suffix = Some(
+                        value
+                            .as_str()
+                            .ok_or_else(|| invalid("a string"))?
+                            .to_string(),
+                    )
+                }
+                "generated-markers" => {
+                    config.generated_markers = Some(
+                        value
+                            .as_array()
+                            .and_then(|values| {
+                                values
+                                    .iter()
+                                    .map(|value| value.as_str().map(str::to_string))
+                                    .collect::<Option<Vec<_>>>()
+                            })
+                            .ok_or_else(|| invalid("an array of strings"))?,
+                    )
+                }
+                _ => return Err(format!("unknown setting `{key}`")),
+            }
+        }
+
+        if config.report.is_some() != config.report_file.is_some() {
+            return Err("`report` and `report-file` must be given together".to_string());
+        }
+
+        Ok(config)
+    }
+}
+
+/// A configuration file that could not be read.
+#[derive(Clone, Debug)]
+pub(crate) struct InvalidConfig {
+    pub path:  PathBuf,
+    pub error: String,
+}
+
+impl std::fmt::Display for InvalidConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Invalid configuration file at {}: {}",
+            self.path.display(),
+            self.error
+        )
+    }
+}
+
+/// The configuration that applies to a directory, if any.
+type Nearest = Result<Option<Arc<Config>>, InvalidConfig>;
+
+/// The configuration files found so far, by directory.
+#[derive(Default)]
+pub(crate) struct Configs {
+    nearest: Mutex<HashMap<PathBuf, Nearest>>,
+}
+
+impl Configs {
+    /// The configuration that applies to the files in `dir`: the one in it,
+    /// or else the one of its nearest ancestor.
+    pub(crate) fn nearest(&self, dir: &Path) -> Nearest {
+        let dir = crate::filter::absolute(dir);
+
+        if let Some(config) = self.nearest.lock().unwrap().get(&dir) {
+            return config.clone();
+        }
+
+        let config = match Configs::read(&dir) {
+            Ok(Some(config)) => Ok(Some(Arc::new(config))),
+            Ok(None) => match dir.parent() {
+                Some(parent) => self.nearest(parent),
+                None => Ok(None),
+            },
+            Err(error) => Err(error),
+        };
+
+        self.nearest.lock().unwrap().insert(dir, config.clone());
+        config
+    }
+
+    fn read(dir: &Path) -> Result<Option<Config>, InvalidConfig> {
+        let files = std::iter::once((CONFIG_FILE, false))
+            .chain(SHARED_CONFIG_FILES.into_iter().map(|file| (file, true)));
+
+        for (file, shared) in files {
+            let path = dir.join(file);
+            if !path.is_file() {
+                continue;
+            }
+
+            match Config::read(&path, shared) {
+                Ok(Some(config)) => return Ok(Some(config)),
+                Ok(None) => {}
+                Err(error) => return Err(InvalidConfig { path, error }),
+            }
+        }
+
+        Ok(None)
+    }
+}
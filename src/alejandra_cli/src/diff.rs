@@ -0,0 +1,176 @@
+/// Number of unchanged lines shown around each change.
+const CONTEXT_LINES: usize = 3;
+
+const BOLD: &str = "\x1b[1m";
+const CYAN: &str = "\x1b[36m";
+const GREEN: &str = "\x1b[32m";
+const RED: &str = "\x1b[31m";
+const RESET: &str = "\x1b[0m";
+
+/// A line of `before` or `after`, as compared by [`lines`].
+enum Line<'a> {
+    /// In both.
+    Both(&'a str),
+    /// Only in `before`.
+    Left(&'a str),
+    /// Only in `after`.
+    Right(&'a str),
+}
+
+/// Renders the changes from `before` to `after` as a unified diff,
+/// optionally colored with ANSI escape sequences.
+///
+/// Returns an empty string if there are no changes.
+pub(crate) fn unified(path: &str, before: &str, after: &str, color: bool) -> String {
+    let lines = lines(before, after);
+    let hunks = hunks(&lines, CONTEXT_LINES);
+
+    if hunks.is_empty() {
+        return String::new();
+    }
+
+    let paint = |style: &str, text: String| {
+        if color && !style.is_empty() {
+            format!("{style}{text}{RESET}\n")
+        } else {
+            format!("{text}\n")
+        }
+    };
+
+    let mut output = String::new();
+    output.push_str(&paint(BOLD, format!("--- {path}")));
+    output.push_str(&paint(BOLD, format!("+++ {path}")));
+
+    let (mut before_line, mut after_line) = (0, 0);
+    let mut position = 0;
+    for (start, end) in hunks {
+        for line in &lines[position..start] {
+            if let Line::Both(_) = line {
+                before_line += 1;
+                after_line += 1;
+            }
+        }
+
+        let before_len = lines[start..end]
+            .iter()
+            .filter(|line| !matches!(line, Line::Right(_)))
+            .count();
+        let after_len = lines[start..end]
+            .iter()
+            .filter(|line| !matches!(line, Line::Left(_)))
+            .count();
+        output.push_str(&paint(
+            CYAN,
+            format!(
+                "@@ -{},{before_len} +{},{after_len} @@",
+                if before_len == 0 {
+                    before_line
+                } else {
+                    before_line + 1
+                },
+                if after_len == 0 {
+                    after_line
+                } else {
+                    after_line + 1
+                },
+            ),
+        ));
+
+        for line in &lines[start..end] {
+            let (style, prefix, text) = match line {
+                Line::Both(text) => {
+                    before_line += 1;
+                    after_line += 1;
+                    ("", ' ', text)
+                }
+                Line::Left(text) => {
+                    before_line += 1;
+                    (RED, '-', text)
+                }
+                Line::Right(text) => {
+                    after_line += 1;
+                    (GREEN, '+', text)
+                }
+            };
+
+            match text.strip_suffix('\n') {
+                Some(text) => output.push_str(&paint(style, format!("{prefix}{text}"))),
+                None => {
+                    output.push_str(&paint(style, format!("{prefix}{text}")));
+                    output.push_str("\\ No newline at end of file\n");
+                }
+            }
+        }
+
+        position = end;
+    }
+
+    output
+}
+
+/// Lines of `before` that change in `after`, as ranges from the first
+/// to the last line, both inclusive and starting at 1.
+///
+/// Lines inserted in `after` are attributed to the line before them.
+pub(crate) fn changed_lines(before: &str, after: &str) -> Vec<(usize, usize)> {
+    let lines = lines(before, after);
+
+    let mut before_line = 0;
+    let mut position = 0;
+    let mut changed_lines = Vec::new();
+    for (start, end) in hunks(&lines, 0) {
+        before_line += lines[position..start].len();
+
+        let before_len = lines[start..end]
+            .iter()
+            .filter(|line| !matches!(line, Line::Right(_)))
+            .count();
+        changed_lines.push(match before_len {
+            0 => (before_line.max(1), before_line.max(1)),
+            _ => (before_line + 1, before_line + before_len),
+        });
+
+        before_line += before_len;
+        position = end;
+    }
+
+    changed_lines
+}
+
+/// The lines of `before` and `after`, in order,
+/// telling which are in both and which only in one of them.
+///
+/// Uses the linear space variant of Myers' algorithm,
+/// so that large files with few changes stay cheap.
+fn lines<'a>(before: &'a str, after: &'a str) -> Vec<Line<'a>> {
+    let before_lines: Vec<&str> = before.split_inclusive('\n').collect();
+    let after_lines: Vec<&str> = after.split_inclusive('\n').collect();
+
+    similar::capture_diff_slices(similar::Algorithm::Myers, &before_lines, &after_lines)
+        .iter()
+        .flat_map(|op| op.iter_changes(&before_lines, &after_lines))
+        .map(|change| match change.tag() {
+            similar::ChangeTag::Equal => Line::Both(change.value()),
+            similar::ChangeTag::Delete => Line::Left(change.value()),
+            similar::ChangeTag::Insert => Line::Right(change.value()),
+        })
+        .collect()
+}
+
+/// Ranges of `lines` with changes, surrounded by `context` unchanged lines.
+fn hunks(lines: &[Line], context: usize) -> Vec<(usize, usize)> {
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        if matches!(line, Line::Both(_)) {
+            continue;
+        }
+
+        let end = (index + 1 + context).min(lines.len());
+        match hunks.last_mut() {
+            Some((_, hunk_end)) if index <= *hunk_end + context => *hunk_end = end,
+            _ => hunks.push((index.saturating_sub(context), end)),
+        }
+    }
+
+    hunks
+}
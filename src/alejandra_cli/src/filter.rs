@@ -0,0 +1,188 @@
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::config::Configs;
+use crate::config::InvalidConfig;
+
+/// A pattern for --include and --exclude, as given in the command line
+/// or in a configuration file.
+///
+/// Patterns are matched against paths relative to the directory they were
+/// given in, with components separated by `/`.
+#[derive(Clone, Debug)]
+pub(crate) struct Pattern {
+    /// The pattern as it was written.
+    pub source: String,
+    matcher:    Matcher,
+}
+
+#[derive(Clone, Debug)]
+enum Matcher {
+    /// A file or directory, which matches everything inside of it.
+    Path(PathBuf),
+    /// A glob that matches the whole relative path.
+    Glob(globset::GlobMatcher),
+    /// A regular expression that matches anywhere in the relative path.
+    Regex(regex::Regex),
+}
+
+impl Pattern {
+    /// Parses a pattern given in the current directory.
+    pub(crate) fn parse(value: &str) -> Result<Pattern, String> {
+        Pattern::parse_in(&absolute(Path::new(".")), value)
+    }
+
+    /// Parses `regex:REGEX`, `glob:GLOB` and `path:PATH`,
+    /// or a glob if it contains any of `*?[`, or a path otherwise.
+    pub(crate) fn parse_in(base: &Path, value: &str) -> Result<Pattern, String> {
+        // Like in git, `*` and `?` do not match `/`, and `**` matches any
+        // number of directories when it is a whole component of the path.
+        // Unlike in git, a set like `[/]` does match `/`.
+        let glob = |glob: &str| {
+            globset::GlobBuilder::new(glob.trim_end_matches('/'))
+                .literal_separator(true)
+                .backslash_escape(true)
+                .build()
+                .map(|glob| Matcher::Glob(glob.compile_matcher()))
+                .map_err(|error| error.to_string())
+        };
+
+        let matcher = if let Some(regex) = value.strip_prefix("regex:") {
+            regex::Regex::new(regex)
+                .map(Matcher::Regex)
+                .map_err(|error| error.to_string())?
+        } else if let Some(value) = value.strip_prefix("glob:") {
+            glob(value)?
+        } else if let Some(path) = value.strip_prefix("path:") {
+            Matcher::Path(absolute(&base.join(path)))
+        } else if value.contains(['*', '?', '[']) {
+            glob(value)?
+        } else {
+            Matcher::Path(absolute(&base.join(value)))
+        };
+
+        Ok(Pattern {
+            source: value.to_string(),
+            matcher,
+        })
+    }
+
+    fn matches(&self, path: &Path, relative: &str) -> bool {
+        match &self.matcher {
+            Matcher::Path(pattern) => path.starts_with(pattern),
+            Matcher::Glob(glob) => glob.is_match(relative),
+            Matcher::Regex(regex) => regex.is_match(relative),
+        }
+    }
+}
+
+/// Patterns given in a directory.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Patterns {
+    pub base:    PathBuf,
+    pub include: Vec<Pattern>,
+    pub exclude: Vec<Pattern>,
+}
+
+impl Patterns {
+    fn excludes(&self, path: &Path) -> bool {
+        let relative = relative(&self.base, path);
+
+        self.exclude
+            .iter()
+            .any(|pattern| pattern.matches(path, &relative))
+    }
+
+    fn includes(&self, path: &Path) -> bool {
+        let relative = relative(&self.base, path);
+
+        self.include
+            .iter()
+            .any(|pattern| pattern.matches(path, &relative))
+    }
+}
+
+/// Decides which of the files and directories found are formatted,
+/// according to the command line, and to the configuration file
+/// that applies to each of them.
+pub(crate) struct Filter {
+    command_line: Patterns,
+    configs:      Arc<Configs>,
+}
+
+impl Filter {
+    pub(crate) fn new(include: Vec<Pattern>, exclude: Vec<Pattern>, configs: Arc<Configs>) -> Filter {
+        Filter {
+            command_line: Patterns {
+                base: absolute(Path::new(".")),
+                include,
+                exclude,
+            },
+            configs,
+        }
+    }
+
+    /// Whether `path` matches an exclude pattern,
+    /// in which case nothing inside of it is walked.
+    pub(crate) fn excludes(&self, path: &Path) -> Result<bool, InvalidConfig> {
+        if self.command_line.excludes(path) {
+            return Ok(true);
+        }
+
+        Ok(self
+            .configs
+            .nearest(path.parent().unwrap_or(path))?
+            .is_some_and(|config| config.patterns.excludes(path)))
+    }
+
+    /// Whether the file at `path` matches an include pattern,
+    /// or there are none. Those of the command line take precedence.
+    pub(crate) fn includes(&self, path: &Path) -> Result<bool, InvalidConfig> {
+        if !self.command_line.include.is_empty() {
+            return Ok(self.command_line.includes(path));
+        }
+
+        Ok(match self.configs.nearest(path.parent().unwrap_or(path))? {
+            Some(config) if !config.patterns.include.is_empty() => config.patterns.includes(path),
+            _ => true,
+        })
+    }
+
+    /// Whether the file at `path`, found without walking directories,
+    /// is formatted: it is included and neither it nor any of its parent
+    /// directories are excluded.
+    pub(crate) fn allows(&self, path: &Path) -> Result<bool, InvalidConfig> {
+        let path = absolute(path);
+
+        if !self.includes(&path)? {
+            return Ok(false);
+        }
+        for ancestor in path
+            .ancestors()
+            .take_while(|ancestor| *ancestor != self.command_line.base && ancestor.parent().is_some())
+        {
+            if self.excludes(ancestor)? {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+/// `path` relative to `base` if it is inside of it,
+/// with components separated by `/`.
+fn relative(base: &Path, path: &Path) -> String {
+    let path = path.strip_prefix(base).unwrap_or(path);
+
+    path.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Makes `path` absolute, without resolving symbolic links.
+pub(crate) fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
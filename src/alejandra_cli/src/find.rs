@@ -1,14 +1,102 @@
+use std::cell::RefCell;
 use std::collections::HashSet;
-use std::process::exit;
+use std::io::Read;
+use std::path::Path;
+use std::path::PathBuf;
 
-pub(crate) fn nix_files(include: &[&str], exclude: &[String]) -> Vec<String> {
-    let include: HashSet<_> = include.iter().flat_map(|s| nix_files_in_path(s)).collect();
-    let exclude: HashSet<_> = exclude.iter().flat_map(|s| nix_files_in_path(s)).collect();
+use crate::config::InvalidConfig;
+use crate::filter::Filter;
+use crate::ignore::Ignores;
+use crate::ignore::ListedIgnores;
 
-    let mut paths: Vec<_> = include
-        .difference(&exclude)
-        .cloned()
-        .map(|path| (len_of(&path), path))
+/// The Nix files in `include`, the largest first,
+/// and the entries that could not be read along the way.
+pub(crate) fn nix_files(
+    include: &[PathBuf],
+    filter: &Filter,
+    honor_ignores: bool,
+) -> (Vec<PathBuf>, Vec<(PathBuf, std::io::Error)>) {
+    let mut unreadable = Vec::new();
+
+    let include: HashSet<_> = include
+        .iter()
+        .flat_map(|path| nix_files_in_path(path, filter, honor_ignores, &mut unreadable))
+        .collect();
+
+    (largest_first(include, &mut unreadable), unreadable)
+}
+
+/// The Nix files in `listed`, which are not walked even if they are
+/// directories, the largest first, and those that could not be read.
+///
+/// Like the files found walking directories, those excluded or ignored
+/// are skipped.
+pub(crate) fn listed_nix_files(
+    listed: Vec<PathBuf>,
+    filter: &Filter,
+    honor_ignores: bool,
+) -> (Vec<PathBuf>, Vec<(PathBuf, std::io::Error)>) {
+    let mut unreadable = Vec::new();
+    let mut ignores = ListedIgnores::new();
+
+    let listed: HashSet<_> = listed
+        .into_iter()
+        .filter(|path| {
+            path.as_os_str().as_encoded_bytes().ends_with(b".nix")
+                && allowed(filter.allows(path), &mut unreadable)
+                && !(honor_ignores && ignores.ignores(path))
+        })
+        .collect();
+    add_unreadable(ignores.unreadable, &mut unreadable);
+
+    (largest_first(listed, &mut unreadable), unreadable)
+}
+
+/// Reads a list of paths separated by `separator` from the file at
+/// `source`, or from stdin if it is `-`.
+pub(crate) fn read_list(source: &Path, separator: u8) -> std::io::Result<Vec<PathBuf>> {
+    let content = if source == Path::new("-") {
+        let mut content = Vec::new();
+        std::io::stdin().read_to_end(&mut content)?;
+        content
+    } else {
+        std::fs::read(source)?
+    };
+
+    Ok(content
+        .split(|byte| *byte == separator)
+        .filter(|path| !path.is_empty())
+        .map(to_path)
+        .collect())
+}
+
+/// A path given as bytes, which are not necessarily valid UTF-8.
+pub(crate) fn to_path(bytes: &[u8]) -> PathBuf {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+
+        PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+    }
+
+    #[cfg(not(unix))]
+    {
+        PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+    }
+}
+
+/// Sorts `paths` from the largest file to the smallest,
+/// adding those whose size cannot be read to `unreadable`.
+fn largest_first(paths: HashSet<PathBuf>, unreadable: &mut Vec<(PathBuf, std::io::Error)>) -> Vec<PathBuf> {
+    let mut paths: Vec<_> = paths
+        .into_iter()
+        .filter_map(|path| match std::fs::metadata(&path) {
+            Ok(metadata) => Some((metadata.len(), path)),
+            Err(error) => {
+                unreadable.push((path, error));
+                None
+            }
+        })
         .collect();
 
     paths.sort_unstable_by(|(len_a, _), (len_b, _)| len_b.cmp(len_a));
@@ -16,38 +104,114 @@ pub(crate) fn nix_files(include: &[&str], exclude: &[String]) -> Vec<String> {
     paths.into_iter().map(|(_, path)| path).collect()
 }
 
-fn nix_files_in_path(path: &str) -> HashSet<String> {
-    walkdir::WalkDir::new(path)
+fn nix_files_in_path(
+    path: &Path,
+    filter: &Filter,
+    honor_ignores: bool,
+    unreadable: &mut Vec<(PathBuf, std::io::Error)>,
+) -> HashSet<PathBuf> {
+    let root = crate::filter::absolute(path);
+    let mut ignores = honor_ignores.then(|| Ignores::above(&root));
+    // Filled while walking, which borrows `unreadable` too.
+    let invalid_configs = RefCell::new(Vec::new());
+
+    let nix_files = walkdir::WalkDir::new(path)
         .into_iter()
-        .filter_entry(is_nix_file_or_dir)
+        .filter_entry(|entry| {
+            let is_dir = entry.file_type().is_dir();
+            let absolute = root.join(entry.path().strip_prefix(path).unwrap_or(entry.path()));
+
+            // Excluded directories are not walked at all.
+            if !allowed(
+                filter.excludes(&absolute).map(|excluded| !excluded),
+                &mut invalid_configs.borrow_mut(),
+            ) {
+                return false;
+            }
+
+            let ignores = match ignores.as_mut() {
+                Some(ignores) => ignores,
+                None => return is_nix_file_or_dir(entry),
+            };
+
+            ignores.leave(entry.depth());
+            // What is given in the command line is never ignored.
+            if entry.depth() > 0 && (entry.file_name() == ".git" || ignores.is_ignored(&absolute, is_dir)) {
+                return false;
+            }
+            if is_dir {
+                ignores.enter(entry.depth() + 1, &absolute);
+            }
+
+            is_nix_file_or_dir(entry)
+        })
         .filter_map(|entry| match entry {
             Ok(entry) => Some(entry),
-            Err(_) => None,
+            Err(error) => {
+                let path = error.path().unwrap_or(path).to_path_buf();
+                let error = error
+                    .into_io_error()
+                    .unwrap_or_else(|| std::io::Error::other("File system loop found"));
+                unreadable.push((path, error));
+                None
+            }
         })
         .filter(is_nix_file)
-        .map(to_full_path)
-        .collect()
-}
+        .filter(|entry| {
+            allowed(
+                filter.includes(&root.join(entry.path().strip_prefix(path).unwrap_or(entry.path()))),
+                &mut invalid_configs.borrow_mut(),
+            )
+        })
+        .map(walkdir::DirEntry::into_path)
+        .collect();
 
-fn is_nix_file(entry: &walkdir::DirEntry) -> bool {
-    entry.file_type().is_file() && entry.file_name().to_str().unwrap().ends_with(".nix")
-}
+    add_unreadable(invalid_configs.into_inner(), unreadable);
+    if let Some(ignores) = ignores {
+        add_unreadable(ignores.unreadable, unreadable);
+    }
 
-fn is_nix_file_or_dir(entry: &walkdir::DirEntry) -> bool {
-    entry.file_type().is_dir() || is_nix_file(entry)
+    nix_files
 }
 
-fn to_full_path(entry: walkdir::DirEntry) -> String {
-    entry.path().to_str().unwrap().to_string()
+/// Adds the files in `found` to `unreadable`,
+/// once however many times they were found.
+fn add_unreadable(found: Vec<(PathBuf, std::io::Error)>, unreadable: &mut Vec<(PathBuf, std::io::Error)>) {
+    for (path, error) in found {
+        if !unreadable.iter().any(|(unreadable, _)| *unreadable == path) {
+            unreadable.push((path, error));
+        }
+    }
 }
 
-fn len_of(path: &str) -> u64 {
-    match std::fs::metadata(&path) {
-        Ok(meta) => meta.len(),
-        Err(err) => {
-            eprintln!("Could not get the size of file at: {path}");
-            eprintln!("Got error: {err}");
-            exit(1);
+/// Whether the filter allows a path, or else adds the configuration file
+/// that could not be read to `unreadable`, once for all the files it
+/// applies to.
+pub(crate) fn allowed(
+    allowed: Result<bool, InvalidConfig>,
+    unreadable: &mut Vec<(PathBuf, std::io::Error)>,
+) -> bool {
+    match allowed {
+        Ok(allowed) => allowed,
+        Err(invalid) => {
+            if !unreadable.iter().any(|(path, _)| *path == invalid.path) {
+                unreadable.push((
+                    invalid.path,
+                    std::io::Error::new(std::io::ErrorKind::InvalidData, invalid.error),
+                ));
+            }
+            false
         }
     }
 }
+
+fn is_nix_file(entry: &walkdir::DirEntry) -> bool {
+    // Symbolic links are followed only if given in the command line.
+    let is_file = entry.file_type().is_file() || (entry.depth() == 0 && entry.path_is_symlink());
+
+    is_file && entry.file_name().as_encoded_bytes().ends_with(b".nix")
+}
+
+fn is_nix_file_or_dir(entry: &walkdir::DirEntry) -> bool {
+    entry.file_type().is_dir() || is_nix_file(entry)
+}
@@ -0,0 +1,27 @@
+/// What tools that generate Nix files usually say in their leading comments,
+/// unless the configuration says otherwise.
+pub(crate) const DEFAULT_MARKERS: [&str; 2] = ["@generated", "DO NOT EDIT"];
+
+/// Whether the comments at the start of `text` contain any of `markers`,
+/// which means that a tool generated it and it should be left alone.
+pub(crate) fn is_generated(text: &str, markers: &[String]) -> bool {
+    if markers.is_empty() {
+        return false;
+    }
+
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+
+    rnix::tokenizer::Tokenizer::new(text)
+        .take_while(|(kind, _)| {
+            matches!(
+                kind,
+                rnix::SyntaxKind::TOKEN_COMMENT | rnix::SyntaxKind::TOKEN_WHITESPACE
+            )
+        })
+        .filter(|(kind, _)| *kind == rnix::SyntaxKind::TOKEN_COMMENT)
+        .any(|(_, comment)| {
+            markers
+                .iter()
+                .any(|marker| comment.contains(marker.as_str()))
+        })
+}
@@ -0,0 +1,432 @@
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Read;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Child;
+use std::process::ChildStdin;
+use std::process::ChildStdout;
+use std::process::Command;
+use std::process::Stdio;
+use std::sync::Mutex;
+
+use alejandra::format::Status;
+
+/// Which files to select from the git repository in the current directory,
+/// as given in the command line.
+#[derive(Clone, Default)]
+pub(crate) struct Selection {
+    pub staged:        bool,
+    pub changed_since: Option<String>,
+    pub untracked:     bool,
+}
+
+impl Selection {
+    pub(crate) fn is_empty(&self) -> bool {
+        !self.staged && self.changed_since.is_none() && !self.untracked
+    }
+
+    /// Lists the Nix files in this selection of the repository at `toplevel`,
+    /// restricted to `pathspecs`, relative to the current directory when
+    /// possible.
+    pub(crate) fn nix_files(&self, toplevel: &Path, pathspecs: &[PathBuf]) -> Result<Vec<PathBuf>, String> {
+        // All of these are relative to the top level of the repository,
+        // even with `diff.relative` configured.
+        let mut names: Vec<PathBuf> = Vec::new();
+        if self.staged {
+            names.extend(list(
+                &[
+                    "diff",
+                    "--cached",
+                    "--name-only",
+                    "--no-relative",
+                    "-z",
+                    "--diff-filter=ACMR",
+                ],
+                pathspecs,
+            )?);
+        }
+        if let Some(revision) = &self.changed_since {
+            names.extend(list(
+                &[
+                    "diff",
+                    "--name-only",
+                    "--no-relative",
+                    "-z",
+                    "--diff-filter=ACMR",
+                    revision,
+                ],
+                pathspecs,
+            )?);
+        }
+        if self.untracked {
+            names.extend(list(
+                &["ls-files", "--others", "--exclude-standard", "--full-name", "-z"],
+                pathspecs,
+            )?);
+        }
+
+        names.sort();
+        names.dedup();
+
+        let current_dir = std::env::current_dir()
+            .and_then(std::fs::canonicalize)
+            .map_err(|error| format!("Unable to get the current directory: {error}"))?;
+
+        Ok(names
+            .into_iter()
+            .filter(|name| name.as_os_str().as_encoded_bytes().ends_with(b".nix"))
+            .map(|name| {
+                let path = toplevel.join(name);
+                path.strip_prefix(&current_dir)
+                    .map(Path::to_path_buf)
+                    .unwrap_or(path)
+            })
+            .collect())
+    }
+}
+
+/// The formatted content of staged files, written to the index at once when
+/// all of them are formatted, because git cannot update the index from
+/// several processes at the same time.
+pub(crate) struct Index {
+    toplevel: PathBuf,
+    /// The mode of each file in the index, by name.
+    modes:    HashMap<String, String>,
+    /// The names and formatted content of the files to stage.
+    staged:   Mutex<Vec<(String, String)>>,
+    /// Reads the staged content of the files.
+    cat_file: Mutex<CatFile>,
+}
+
+impl Index {
+    /// Reads the index of the repository at `toplevel`.
+    pub(crate) fn new(toplevel: PathBuf) -> Result<Self, String> {
+        let entries = git(
+            &[
+                OsStr::new("-C"),
+                toplevel.as_os_str(),
+                OsStr::new("ls-files"),
+                OsStr::new("--stage"),
+                OsStr::new("-z"),
+            ],
+            None,
+        )?;
+
+        // Each entry is `<mode> <object> <stage>\t<name>`.
+        let modes = entries
+            .split(|byte| *byte == 0)
+            .filter_map(|entry| {
+                let entry = std::str::from_utf8(entry).ok()?;
+                let (info, name) = entry.split_once('\t')?;
+                let (mode, _) = info.split_once(' ')?;
+                Some((name.to_string(), mode.to_string()))
+            })
+            .collect();
+
+        let cat_file = CatFile::new(&toplevel)?;
+
+        Ok(Self {
+            toplevel,
+            modes,
+            staged: Mutex::default(),
+            cat_file: Mutex::new(cat_file),
+        })
+    }
+
+    /// Writes the formatted content of the staged files to the object
+    /// database, and records it in the index.
+    pub(crate) fn write(&self) -> Result<(), String> {
+        let staged = self.staged.lock().unwrap();
+        if staged.is_empty() {
+            return Ok(());
+        }
+
+        // `git hash-object` only writes several objects at once from files.
+        let dir = std::env::temp_dir().join(format!("alejandra-staged-{}", std::process::id()));
+        let objects = std::fs::create_dir(&dir)
+            .and_then(|()| {
+                staged
+                    .iter()
+                    .enumerate()
+                    .map(|(number, (_, content))| {
+                        let path = dir.join(number.to_string());
+                        std::fs::write(&path, content).map(|()| path)
+                    })
+                    .collect::<std::io::Result<Vec<PathBuf>>>()
+            })
+            .map_err(|error| format!("Unable to write to {}: {error}", dir.display()))
+            .and_then(|paths| {
+                let mut stdin = Vec::new();
+                for path in paths {
+                    stdin.extend_from_slice(path.as_os_str().as_encoded_bytes());
+                    stdin.push(b'\n');
+                }
+
+                git(
+                    &[
+                        OsStr::new("-C"),
+                        self.toplevel.as_os_str(),
+                        OsStr::new("hash-object"),
+                        OsStr::new("-w"),
+                        OsStr::new("--no-filters"),
+                        OsStr::new("--stdin-paths"),
+                    ],
+                    Some(&stdin),
+                )
+            });
+        let _ = std::fs::remove_dir_all(&dir);
+        let objects = objects?;
+
+        // Entries in the format of `git update-index -z --index-info`.
+        let mut entries = Vec::new();
+        for ((name, _), object) in staged.iter().zip(objects.split(|byte| *byte == b'\n')) {
+            entries.extend_from_slice(self.modes[name].as_bytes());
+            entries.push(b' ');
+            entries.extend_from_slice(object);
+            entries.push(b'\t');
+            entries.extend_from_slice(name.as_bytes());
+            entries.push(0);
+        }
+
+        git(
+            &[
+                OsStr::new("-C"),
+                self.toplevel.as_os_str(),
+                OsStr::new("update-index"),
+                OsStr::new("-z"),
+                OsStr::new("--index-info"),
+            ],
+            Some(&entries),
+        )?;
+
+        Ok(())
+    }
+
+    /// Records `content` as the new content of `name`,
+    /// to be written by [`Index::write`].
+    fn stage(&self, name: &str, content: &str) -> Result<(), String> {
+        if !self.modes.contains_key(name) {
+            return Err(format!("{name} is not in the index"));
+        }
+
+        self.staged
+            .lock()
+            .unwrap()
+            .push((name.to_string(), content.to_string()));
+
+        Ok(())
+    }
+
+    /// The name of `path` in the index, relative to the top level of the
+    /// repository.
+    fn name(&self, path: &Path) -> Result<String, String> {
+        let parent = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        let path = std::fs::canonicalize(parent)
+            .map(|parent| parent.join(path.file_name().unwrap_or_default()))
+            .map_err(|error| format!("Unable to resolve {}: {error}", path.display()))?;
+
+        match path.strip_prefix(&self.toplevel) {
+            Ok(name) => name
+                .to_str()
+                .map(str::to_string)
+                .ok_or_else(|| format!("{} is not valid UTF-8", name.display())),
+            Err(_) => Err(format!(
+                "{} is outside of the repository at {}",
+                path.display(),
+                self.toplevel.display()
+            )),
+        }
+    }
+}
+
+/// A `git cat-file --batch` process,
+/// that reads objects without starting a process for each of them.
+struct CatFile {
+    child:  Child,
+    stdin:  Option<ChildStdin>,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl CatFile {
+    fn new(toplevel: &Path) -> Result<Self, String> {
+        let mut child = Command::new("git")
+            .arg("-C")
+            .arg(toplevel)
+            .args(["cat-file", "--batch"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|error| format!("Unable to run git: {error}"))?;
+
+        Ok(Self {
+            stdin: child.stdin.take(),
+            stdout: BufReader::new(child.stdout.take().unwrap()),
+            child,
+        })
+    }
+
+    /// The content of the object called `name`.
+    fn read(&mut self, name: &str) -> Result<Vec<u8>, String> {
+        let error = |error: std::io::Error| format!("Unable to read {name} from git: {error}");
+
+        let stdin = self.stdin.as_mut().unwrap();
+        writeln!(stdin, "{name}")
+            .and_then(|()| stdin.flush())
+            .map_err(error)?;
+
+        // The object is `<object> <type> <size>\n<content>\n`,
+        // or `<name> missing\n`.
+        let mut header = String::new();
+        self.stdout.read_line(&mut header).map_err(error)?;
+        let size = header
+            .trim_end()
+            .rsplit_once(' ')
+            .and_then(|(_, size)| size.parse::<usize>().ok())
+            .ok_or_else(|| format!("{name} is not in the repository"))?;
+
+        let mut content = vec![0; size + 1];
+        self.stdout.read_exact(&mut content).map_err(error)?;
+        content.pop();
+
+        Ok(content)
+    }
+}
+
+impl Drop for CatFile {
+    fn drop(&mut self) {
+        // Closing its input stops it.
+        drop(self.stdin.take());
+        let _ = self.child.wait();
+    }
+}
+
+/// Formats the content of `path` that is staged in the index,
+/// like [`alejandra::format::in_fs_using`] does with the file itself.
+///
+/// When writing in-place, the formatted content is added to `index`,
+/// and written to the work tree too if it has no unstaged changes.
+pub(crate) fn format_staged<F>(
+    path: &Path,
+    in_place: bool,
+    backup_suffix: Option<&str>,
+    index: &Index,
+    formatter: F,
+) -> Status
+where
+    F: FnOnce(String, String) -> (Status, String),
+{
+    let name = match index.name(path) {
+        Ok(name) => name,
+        Err(error) => return Status::from(std::io::Error::other(error)),
+    };
+
+    if name.contains('\n') {
+        return Status::from(std::io::Error::other(format!(
+            "{name} has a line break in its name"
+        )));
+    }
+    let staged = index.cat_file.lock().unwrap().read(&format!(":{name}"));
+    let before = match staged {
+        Ok(before) => match String::from_utf8(before) {
+            Ok(before) => before,
+            Err(_) => {
+                return Status::from(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("The staged content of {name} is not valid UTF-8"),
+                ));
+            }
+        },
+        Err(error) => return Status::from(std::io::Error::other(error)),
+    };
+
+    let (status, data) = formatter(path.to_string_lossy().into_owned(), before.clone());
+
+    if in_place {
+        if let Status::Changed(true) = status {
+            if let Err(error) = index.stage(&name, &data) {
+                return Status::from(std::io::Error::other(error));
+            }
+
+            if std::fs::read_to_string(path).is_ok_and(|work_tree| work_tree == before) {
+                if let Err(error) = alejandra::write::atomically(path, &data, backup_suffix) {
+                    return Status::from(error);
+                }
+            }
+        }
+    }
+
+    status
+}
+
+/// The top level of the git repository in the current directory.
+pub(crate) fn toplevel() -> Result<PathBuf, String> {
+    let output = git(&["rev-parse", "--show-toplevel"], None)?;
+
+    Ok(crate::find::to_path(
+        output.strip_suffix(b"\n").unwrap_or(&output),
+    ))
+}
+
+/// Runs a git command that prints NUL-separated names,
+/// restricted to `pathspecs`.
+fn list(args: &[&str], pathspecs: &[PathBuf]) -> Result<Vec<PathBuf>, String> {
+    let mut args: Vec<&OsStr> = args.iter().map(OsStr::new).collect();
+    args.push(OsStr::new("--"));
+    args.extend(pathspecs.iter().map(|pathspec| pathspec.as_os_str()));
+
+    let output = git(&args, None)?;
+
+    Ok(output
+        .split(|byte| *byte == 0)
+        .filter(|name| !name.is_empty())
+        .map(crate::find::to_path)
+        .collect())
+}
+
+fn git<S: AsRef<OsStr>>(args: &[S], stdin: Option<&[u8]>) -> Result<Vec<u8>, String> {
+    let mut child = Command::new("git")
+        .args(args)
+        .stdin(if stdin.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|error| format!("Unable to run git: {error}"))?;
+
+    if let Some(stdin) = stdin {
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(stdin)
+            .map_err(|error| format!("Unable to write to git: {error}"))?;
+    }
+
+    let output = child
+        .wait_with_output()
+        .map_err(|error| format!("Unable to run git: {error}"))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "`git {}` failed: {}",
+            args.iter()
+                .map(|arg| arg.as_ref().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join(" "),
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        ));
+    }
+
+    Ok(output.stdout)
+}
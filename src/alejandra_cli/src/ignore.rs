@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+use std::process::Stdio;
+
+use ignore::gitignore::Gitignore;
+use ignore::gitignore::GitignoreBuilder;
+use ignore::Match;
+
+/// Files with gitignore syntax that are honored in every directory,
+/// from lowest to highest precedence.
+const IGNORE_FILES: [&str; 3] = [".gitignore", ".ignore", ".alejandraignore"];
+
+/// The patterns of an ignore file,
+/// which apply to the paths inside of the directory that contains it.
+#[derive(Clone)]
+struct IgnoreFile {
+    base:      PathBuf,
+    gitignore: Gitignore,
+}
+
+impl IgnoreFile {
+    /// Reads the ignore file at `path`, if it exists,
+    /// adding it to `unreadable` if any of it cannot be read.
+    fn read(base: &Path, path: &Path, unreadable: &mut Vec<(PathBuf, std::io::Error)>) -> Option<IgnoreFile> {
+        if !path.is_file() {
+            return None;
+        }
+
+        // The patterns that can be read apply even so.
+        let mut builder = GitignoreBuilder::new(base);
+        if let Some(error) = builder.add(path) {
+            if !unreadable.iter().any(|(unreadable, _)| unreadable == path) {
+                unreadable.push((
+                    path.to_path_buf(),
+                    std::io::Error::new(std::io::ErrorKind::InvalidData, without_path(error)),
+                ));
+            }
+        }
+
+        Some(IgnoreFile {
+            base:      base.to_path_buf(),
+            gitignore: builder.build().ok()?,
+        })
+    }
+
+    /// Whether `path` is ignored (`Some(true)`) or re-included
+    /// (`Some(false)`) by this file, if any pattern matches it.
+    fn matches(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        path.strip_prefix(&self.base).ok()?;
+
+        match self.gitignore.matched(path, is_dir) {
+            Match::None => None,
+            Match::Ignore(_) => Some(true),
+            Match::Whitelist(_) => Some(false),
+        }
+    }
+}
+
+/// `error` without the path of the ignore file,
+/// which is reported along with it.
+fn without_path(error: ignore::Error) -> ignore::Error {
+    match error {
+        ignore::Error::WithPath { err, .. } => *err,
+        ignore::Error::Partial(errors) => {
+            ignore::Error::Partial(errors.into_iter().map(without_path).collect())
+        }
+        error => error,
+    }
+}
+
+/// The ignore file of the user, for all of their repositories:
+/// `core.excludesFile`, or else `$XDG_CONFIG_HOME/git/ignore`,
+/// like git does.
+fn excludes_file(repository: &Path) -> Option<PathBuf> {
+    let configured = Command::new("git")
+        .arg("-C")
+        .arg(repository)
+        .args(["config", "--path", "core.excludesFile"])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| {
+            let path = output.stdout.strip_suffix(b"\n").unwrap_or(&output.stdout);
+            repository.join(crate::find::to_path(path))
+        });
+
+    configured.or_else(|| {
+        let config_home = std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|config_home| !config_home.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+        Some(config_home.join("git").join("ignore"))
+    })
+}
+
+/// The ignore files that apply to the directories being walked.
+pub(crate) struct Ignores {
+    /// Ignore files, from the outermost to the innermost,
+    /// with the minimum depth in the walk of the entries they apply to.
+    files:                 Vec<(usize, IgnoreFile)>,
+    /// The ignore files that could not be read.
+    pub(crate) unreadable: Vec<(PathBuf, std::io::Error)>,
+}
+
+impl Ignores {
+    /// The ignore files of the ancestors of `root`,
+    /// up to the root of the git repository that contains it, if any.
+    pub(crate) fn above(root: &Path) -> Ignores {
+        let mut ignores = Ignores {
+            files:      Vec::new(),
+            unreadable: Vec::new(),
+        };
+
+        let ancestors: Vec<&Path> = root.ancestors().collect();
+        if let Some(repository) = ancestors.iter().position(|dir| dir.join(".git").exists()) {
+            for dir in ancestors[1..=repository].iter().rev() {
+                ignores.enter(0, dir);
+            }
+        }
+
+        ignores
+    }
+
+    /// Loads the ignore files of `dir`,
+    /// which apply to the entries found from `depth` on in the walk.
+    pub(crate) fn enter(&mut self, depth: usize, dir: &Path) {
+        let mut paths = Vec::new();
+        if dir.join(".git").exists() {
+            paths.extend(excludes_file(dir));
+            paths.push(dir.join(".git").join("info").join("exclude"));
+        }
+        paths.extend(IGNORE_FILES.map(|name| dir.join(name)));
+
+        for path in paths {
+            if let Some(file) = IgnoreFile::read(dir, &path, &mut self.unreadable) {
+                self.files.push((depth, file));
+            }
+        }
+    }
+
+    /// Forgets the ignore files of directories that are not
+    /// ancestors of an entry found at `depth` in the walk.
+    pub(crate) fn leave(&mut self, depth: usize) {
+        while self
+            .files
+            .last()
+            .is_some_and(|(file_depth, _)| *file_depth > depth)
+        {
+            self.files.pop();
+        }
+    }
+
+    pub(crate) fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        self.files
+            .iter()
+            .rev()
+            .find_map(|(_, file)| file.matches(path, is_dir))
+            .unwrap_or(false)
+    }
+}
+
+/// The ignore files that apply to a directory,
+/// for files found without walking directories.
+struct ListedDir {
+    ignores: Ignores,
+    /// Whether the directory, or any of its parents below the current
+    /// directory or the root of the git repository, is ignored.
+    ignored: bool,
+}
+
+/// Decides whether files found without walking directories are ignored,
+/// or any of their parent directories below the current directory is,
+/// according to the ignore files of their ancestors from the root of the
+/// git repository that contains them, or else from the current directory.
+///
+/// The ignore files of each directory are read only once,
+/// however many files are listed in it.
+pub(crate) struct ListedIgnores {
+    current_dir:           PathBuf,
+    /// `None` for the directories outside of the current directory
+    /// and of any git repository.
+    dirs:                  HashMap<PathBuf, Option<ListedDir>>,
+    /// The ignore files that could not be read.
+    pub(crate) unreadable: Vec<(PathBuf, std::io::Error)>,
+}
+
+impl ListedIgnores {
+    pub(crate) fn new() -> ListedIgnores {
+        ListedIgnores {
+            current_dir: crate::filter::absolute(Path::new(".")),
+            dirs:        HashMap::new(),
+            unreadable:  Vec::new(),
+        }
+    }
+
+    pub(crate) fn ignores(&mut self, path: &Path) -> bool {
+        let path = crate::filter::absolute(path);
+        let parent = match path.parent() {
+            Some(parent) => parent,
+            None => return false,
+        };
+
+        self.load(parent);
+        match &self.dirs[parent] {
+            Some(dir) => dir.ignored || path.ends_with(".git") || dir.ignores.is_ignored(&path, false),
+            None => false,
+        }
+    }
+
+    /// Reads the ignore files that apply to `dir`, and to its parents.
+    fn load(&mut self, dir: &Path) {
+        if self.dirs.contains_key(dir) {
+            return;
+        }
+
+        let is_current_dir = dir == self.current_dir;
+        let fresh = || {
+            let mut ignores = Ignores {
+                files:      Vec::new(),
+                unreadable: Vec::new(),
+            };
+            ignores.enter(0, dir);
+            Some(ListedDir {
+                ignores,
+                ignored: false,
+            })
+        };
+
+        let mut listed_dir = if dir.join(".git").exists() {
+            fresh()
+        } else {
+            let parent = dir.parent().map(|parent| {
+                self.load(parent);
+                &self.dirs[parent]
+            });
+
+            match parent {
+                Some(Some(parent)) => {
+                    let mut ignores = Ignores {
+                        files:      parent.ignores.files.clone(),
+                        unreadable: Vec::new(),
+                    };
+                    ignores.enter(0, dir);
+                    // Parents above the current directory are not checked.
+                    let ignored = !is_current_dir
+                        && (parent.ignored || dir.ends_with(".git") || parent.ignores.is_ignored(dir, true));
+
+                    Some(ListedDir { ignores, ignored })
+                }
+                _ if is_current_dir => fresh(),
+                _ => None,
+            }
+        };
+
+        if let Some(listed_dir) = &mut listed_dir {
+            self.unreadable.append(&mut listed_dir.ignores.unreadable);
+        }
+        self.dirs.insert(dir.to_path_buf(), listed_dir);
+    }
+}
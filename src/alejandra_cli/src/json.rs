@@ -0,0 +1,57 @@
+use alejandra::error::Error;
+use alejandra::format::Status;
+use serde_json::json;
+use serde_json::Value;
+
+use crate::cli::FormattedPath;
+
+/// Describes the result of formatting a file,
+/// as printed by `--output-format json` and `--output-format ndjson`.
+pub(crate) fn record(formatted_path: &FormattedPath) -> Value {
+    let (status, errors) = match &formatted_path.status {
+        Status::Changed(false) if formatted_path.skipped => ("skipped", Vec::new()),
+        Status::Changed(false) => ("unchanged", Vec::new()),
+        Status::Changed(true) => ("changed", Vec::new()),
+        Status::Error(Error::Parse(errors)) => (
+            "error",
+            errors
+                .iter()
+                .map(|error| {
+                    json!({
+                        "message": error.message,
+                        "start": {
+                            "offset": u32::from(error.range.start()),
+                            "line": error.start.line,
+                            "column": error.start.column,
+                        },
+                        "end": {
+                            "offset": u32::from(error.range.end()),
+                            "line": error.end.line,
+                            "column": error.end.column,
+                        },
+                    })
+                })
+                .collect(),
+        ),
+        Status::Error(error) => ("error", vec![json!({ "message": error.to_string() })]),
+        Status::InternalError(error) => {
+            let mut record = json!({
+                "message": format!("internal error: {error}"),
+                "kind": error.kind.map(|kind| format!("{kind:?}")),
+            });
+            if let Some(location) = error.location {
+                record["start"] = json!({ "line": location.line, "column": location.column });
+            }
+
+            ("error", vec![record])
+        }
+    };
+
+    json!({
+        "path": formatted_path.path.to_string_lossy(),
+        "status": status,
+        "errors": errors,
+        "omitted_errors": formatted_path.omitted,
+        "duration_ms": formatted_path.duration.as_secs_f64() * 1000.0,
+    })
+}
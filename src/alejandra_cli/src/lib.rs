@@ -1,4 +1,15 @@
 mod ads;
 pub mod cli;
+pub mod config;
+mod diff;
+mod filter;
 mod find;
+mod generated;
+mod git;
+mod ignore;
+mod json;
+mod mode;
+mod panic;
+mod range;
+mod report;
 mod verbosity;
@@ -0,0 +1,213 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use alejandra::format::Status;
+use alejandra::options::FormatOptions;
+
+use crate::config::Config;
+use crate::config::Configs;
+use crate::range::Region;
+
+/// The settings of [`Mode`] given in the command line,
+/// which take precedence over those of the configuration files.
+#[derive(Clone, Default)]
+pub(crate) struct Overrides {
+    pub line_width:        Option<usize>,
+    pub indent_width:      Option<usize>,
+    pub indent_style:      Option<IndentStyle>,
+    pub line_ending:       Option<LineEnding>,
+    pub no_final_newline:  bool,
+    pub verify:            bool,
+    pub verify_idempotent: bool,
+    pub backup_suffix:     Option<String>,
+    pub format_generated:  bool,
+}
+
+impl Overrides {
+    /// `options`, with the formatting settings given in the command line.
+    fn format_options(&self, mut options: FormatOptions) -> FormatOptions {
+        if let Some(line_width) = self.line_width {
+            options = options.with_line_width(line_width);
+        }
+        if let Some(indent_width) = self.indent_width {
+            options = options.with_indent_width(indent_width);
+        }
+        if let Some(indent_style) = self.indent_style {
+            options = options.with_indent_style(indent_style.into());
+        }
+        if let Some(line_ending) = self.line_ending {
+            options = options.with_line_ending(line_ending.into());
+        }
+        if self.no_final_newline {
+            options = options.with_final_newline(false);
+        }
+
+        options
+    }
+}
+
+/// How to format each file, as given in the command line
+/// and in the configuration file that applies to it.
+#[derive(Clone, Default)]
+pub(crate) struct Mode {
+    pub overrides:         Overrides,
+    pub options:           FormatOptions,
+    pub region:            Option<Region>,
+    pub verify:            bool,
+    pub verify_idempotent: bool,
+    /// Format the content staged in the git index instead of the file,
+    /// and collect the content to stage in this index.
+    pub staged:            Option<Arc<crate::git::Index>>,
+    /// Write the formatted content in-place, instead of only checking it.
+    pub in_place:          bool,
+    /// Keep the original of each file written in-place,
+    /// in a file with this suffix appended to its name.
+    pub backup_suffix:     Option<String>,
+    /// Keep only this many syntax errors of each file,
+    /// see [`Mode::limited`].
+    pub max_errors:        usize,
+    /// Leave alone the files whose leading comments contain any of these,
+    /// see [`crate::generated::is_generated`].
+    pub generated_markers: Vec<String>,
+    /// The configuration files found so far,
+    /// see [`Mode::configured_for`].
+    pub configs:           Arc<Configs>,
+}
+
+impl Mode {
+    /// This mode, with the settings of the configuration file nearest to the
+    /// file at `path`.
+    pub(crate) fn configured_for(&self, path: &Path) -> Mode {
+        let path = crate::filter::absolute(path);
+
+        match self.configs.nearest(path.parent().unwrap_or(&path)) {
+            Ok(config) => self.configured(&config.unwrap_or_default()),
+            // Files with a configuration file that is not valid are
+            // reported by the filter, and are not formatted.
+            Err(_) => self.clone(),
+        }
+    }
+
+    /// This mode, with the settings of `config`
+    /// unless they were given in the command line.
+    pub(crate) fn configured(&self, config: &Config) -> Mode {
+        let overrides = &self.overrides;
+
+        Mode {
+            options: overrides.format_options(config.format_options(FormatOptions::default())),
+            verify: overrides.verify || config.verify.unwrap_or(false),
+            verify_idempotent: overrides.verify_idempotent || config.verify_idempotent.unwrap_or(false),
+            backup_suffix: overrides
+                .backup_suffix
+                .clone()
+                .or_else(|| config.backup_suffix.clone()),
+            generated_markers: if overrides.format_generated {
+                Vec::new()
+            } else {
+                config.generated_markers.clone().unwrap_or_else(|| {
+                    crate::generated::DEFAULT_MARKERS
+                        .iter()
+                        .map(|marker| marker.to_string())
+                        .collect()
+                })
+            },
+            ..self.clone()
+        }
+    }
+
+    /// Formats `before`, the content of the file at `path`,
+    /// according to this mode, like [`alejandra::format::in_memory`].
+    pub(crate) fn in_memory(&self, path: String, before: String) -> (Status, String) {
+        let (status, after) = match self.region {
+            Some(region) => {
+                let range = region.text_range(&before);
+                let (status, after, _) =
+                    alejandra::format::in_memory_range(path, before.clone(), range, &self.options);
+                (status, after)
+            }
+            None if self.verify_idempotent => {
+                alejandra::format::check_idempotent(before.clone(), &self.options)
+            }
+            None => alejandra::format::in_memory_with(before.clone(), &self.options),
+        };
+
+        self.verified(before, status, after)
+    }
+
+    /// Keeps the first [`Mode::max_errors`] syntax errors of `status`,
+    /// and tells how many were left out.
+    pub(crate) fn limited(&self, status: Status) -> (Status, usize) {
+        match status {
+            Status::Error(alejandra::error::Error::Parse(mut errors)) => {
+                let omitted = errors.len().saturating_sub(self.max_errors);
+                errors.truncate(self.max_errors);
+
+                (Status::Error(alejandra::error::Error::Parse(errors)), omitted)
+            }
+            status => (status, 0),
+        }
+    }
+
+    /// Makes sure that `after` means the same as `before`, if requested.
+    pub(crate) fn verified(&self, before: String, status: Status, after: String) -> (Status, String) {
+        if !self.verify || !matches!(status, Status::Changed(true)) {
+            return (status, after);
+        }
+
+        match alejandra::format::check_equivalent(&before, &after) {
+            Ok(()) => (status, after),
+            Err(error) => (Status::Error(error), before),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub(crate) enum IndentStyle {
+    Spaces,
+    Tabs,
+}
+
+impl From<IndentStyle> for alejandra::options::IndentStyle {
+    fn from(indent_style: IndentStyle) -> alejandra::options::IndentStyle {
+        match indent_style {
+            IndentStyle::Spaces => alejandra::options::IndentStyle::Spaces,
+            IndentStyle::Tabs => alejandra::options::IndentStyle::Tabs,
+        }
+    }
+}
+
+impl From<alejandra::options::IndentStyle> for IndentStyle {
+    fn from(indent_style: alejandra::options::IndentStyle) -> IndentStyle {
+        match indent_style {
+            alejandra::options::IndentStyle::Spaces => IndentStyle::Spaces,
+            alejandra::options::IndentStyle::Tabs => IndentStyle::Tabs,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub(crate) enum LineEnding {
+    Auto,
+    Lf,
+    Crlf,
+}
+
+impl From<LineEnding> for alejandra::options::LineEnding {
+    fn from(line_ending: LineEnding) -> alejandra::options::LineEnding {
+        match line_ending {
+            LineEnding::Auto => alejandra::options::LineEnding::Auto,
+            LineEnding::Lf => alejandra::options::LineEnding::Lf,
+            LineEnding::Crlf => alejandra::options::LineEnding::Crlf,
+        }
+    }
+}
+
+impl From<alejandra::options::LineEnding> for LineEnding {
+    fn from(line_ending: alejandra::options::LineEnding) -> LineEnding {
+        match line_ending {
+            alejandra::options::LineEnding::Auto => LineEnding::Auto,
+            alejandra::options::LineEnding::Lf => LineEnding::Lf,
+            alejandra::options::LineEnding::Crlf => LineEnding::Crlf,
+        }
+    }
+}
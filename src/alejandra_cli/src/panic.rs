@@ -0,0 +1,26 @@
+use alejandra::error::InternalError;
+
+thread_local! {
+    /// Whether the panics of this thread are caught by [`catch`].
+    static CATCHING: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+}
+
+/// Runs `f`, and turns a panic into an internal error
+/// instead of printing it.
+pub(crate) fn catch<T>(f: impl FnOnce() -> T) -> Result<T, InternalError> {
+    static SILENCE: std::sync::Once = std::sync::Once::new();
+    SILENCE.call_once(|| {
+        let hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            if !CATCHING.with(std::cell::Cell::get) {
+                hook(info);
+            }
+        }));
+    });
+
+    let was_catching = CATCHING.with(|catching| catching.replace(true));
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(f));
+    CATCHING.with(|catching| catching.set(was_catching));
+
+    result.map_err(|payload| InternalError::from_panic(payload.as_ref()))
+}
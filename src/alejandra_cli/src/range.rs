@@ -0,0 +1,63 @@
+/// A part of a file to format, as given in the command line.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Region {
+    /// Byte offsets, as `START:END`.
+    Bytes(usize, usize),
+    /// Line numbers starting at 1, both inclusive, as `FIRST-LAST`.
+    Lines(usize, usize),
+}
+
+impl Region {
+    pub(crate) fn parse_bytes(value: &str) -> Result<Region, String> {
+        let (start, end) = parse_pair(value, ':')?;
+
+        if start > end {
+            return Err(format!("start must not be greater than end: {value}"));
+        }
+
+        Ok(Region::Bytes(start, end))
+    }
+
+    pub(crate) fn parse_lines(value: &str) -> Result<Region, String> {
+        let (first, last) = parse_pair(value, '-')?;
+
+        if first == 0 || first > last {
+            return Err(format!("expected 1 <= FIRST <= LAST: {value}"));
+        }
+
+        Ok(Region::Lines(first, last))
+    }
+
+    pub(crate) fn text_range(&self, source: &str) -> rnix::TextRange {
+        let (start, end) = match *self {
+            Region::Bytes(start, end) => (start, end),
+            Region::Lines(first, last) => {
+                let mut line_starts =
+                    std::iter::once(0).chain(source.match_indices('\n').map(|(index, _)| index + 1));
+
+                let start = line_starts.nth(first - 1).unwrap_or(source.len());
+                let end = line_starts
+                    .nth(last - first)
+                    .map_or(source.len(), |next_line_start| next_line_start - 1);
+
+                (start, end)
+            }
+        };
+
+        let start = start.min(source.len());
+        let end = end.min(source.len()).max(start);
+
+        rnix::TextRange::new((start as u32).into(), (end as u32).into())
+    }
+}
+
+fn parse_pair(value: &str, separator: char) -> Result<(usize, usize), String> {
+    let (left, right) = value
+        .split_once(separator)
+        .ok_or_else(|| format!("expected two numbers separated by `{separator}`: {value}"))?;
+
+    let left = left.parse().map_err(|error| format!("{error}: {left}"))?;
+    let right = right.parse().map_err(|error| format!("{error}: {right}"))?;
+
+    Ok((left, right))
+}
@@ -0,0 +1,279 @@
+use alejandra::error::Error;
+use alejandra::format::Status;
+use serde_json::json;
+use serde_json::Value;
+
+use crate::cli::FormattedPath;
+
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+pub(crate) enum ReportFormat {
+    Sarif,
+    Checkstyle,
+    Junit,
+}
+
+/// Something wrong with a file, at a line and optionally a column.
+struct Finding {
+    rule:     &'static str,
+    message:  String,
+    /// First and last line, starting at 1.
+    lines:    Option<(usize, usize)>,
+    /// First and last column, starting at 1.
+    columns:  Option<(usize, usize)>,
+    /// Whether the finding prevented formatting the file.
+    is_error: bool,
+}
+
+const RULE_FORMATTING: &str = "formatting";
+const RULE_SYNTAX_ERROR: &str = "syntax-error";
+const RULE_ERROR: &str = "error";
+
+/// Writes a report of `formatted_paths` to `path`,
+/// in the given format.
+pub(crate) fn write(
+    format: ReportFormat,
+    path: &str,
+    formatted_paths: &[FormattedPath],
+) -> std::io::Result<()> {
+    let report = match format {
+        ReportFormat::Sarif => sarif(formatted_paths),
+        ReportFormat::Checkstyle => checkstyle(formatted_paths),
+        ReportFormat::Junit => junit(formatted_paths),
+    };
+
+    std::fs::write(path, report)
+}
+
+fn findings(formatted_path: &FormattedPath) -> Vec<Finding> {
+    match &formatted_path.status {
+        Status::Changed(false) => Vec::new(),
+        Status::Changed(true) => formatted_path
+            .changed_lines
+            .iter()
+            .map(|&(first, last)| Finding {
+                rule:     RULE_FORMATTING,
+                message:  if first == last {
+                    format!("Line {first} requires formatting")
+                } else {
+                    format!("Lines {first}-{last} require formatting")
+                },
+                lines:    Some((first, last)),
+                columns:  None,
+                is_error: false,
+            })
+            .collect(),
+        Status::Error(Error::Parse(errors)) => errors
+            .iter()
+            .map(|error| Finding {
+                rule:     RULE_SYNTAX_ERROR,
+                message:  error.message.clone(),
+                lines:    Some((error.start.line, error.end.line)),
+                columns:  Some((error.start.column, error.end.column)),
+                is_error: true,
+            })
+            .collect(),
+        Status::Error(error) => vec![Finding {
+            rule:     RULE_ERROR,
+            message:  error.to_string(),
+            lines:    None,
+            columns:  None,
+            is_error: true,
+        }],
+        Status::InternalError(error) => vec![Finding {
+            rule:     RULE_ERROR,
+            message:  format!("internal error: {error}"),
+            lines:    error
+                .location
+                .map(|location| (location.line, location.line)),
+            columns:  error
+                .location
+                .map(|location| (location.column, location.column)),
+            is_error: true,
+        }],
+    }
+}
+
+/// Static Analysis Results Interchange Format, version 2.1.0.
+fn sarif(formatted_paths: &[FormattedPath]) -> String {
+    let results: Vec<Value> = formatted_paths
+        .iter()
+        .flat_map(|formatted_path| {
+            findings(formatted_path).into_iter().map(|finding| {
+                let mut physical_location = json!({
+                    "artifactLocation": { "uri": formatted_path.path.to_string_lossy() },
+                });
+                if let Some((first, last)) = finding.lines {
+                    physical_location["region"] = json!({ "startLine": first, "endLine": last });
+                }
+                if let Some((first, last)) = finding.columns {
+                    physical_location["region"]["startColumn"] = json!(first);
+                    physical_location["region"]["endColumn"] = json!(last);
+                }
+
+                json!({
+                    "ruleId": finding.rule,
+                    "level": if finding.is_error { "error" } else { "warning" },
+                    "message": { "text": finding.message },
+                    "locations": [{ "physicalLocation": physical_location }],
+                })
+            })
+        })
+        .collect();
+
+    let report = json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "Alejandra",
+                    "version": alejandra::version::VERSION,
+                    "informationUri": "https://github.com/kamadorueda/alejandra",
+                    "rules": [
+                        {
+                            "id": RULE_FORMATTING,
+                            "shortDescription": { "text": "The code does not follow the Alejandra style" },
+                        },
+                        {
+                            "id": RULE_SYNTAX_ERROR,
+                            "shortDescription": { "text": "The code is not valid Nix" },
+                        },
+                        {
+                            "id": RULE_ERROR,
+                            "shortDescription": { "text": "The file could not be formatted" },
+                        },
+                    ],
+                },
+            },
+            "results": results,
+        }],
+    });
+
+    format!("{report:#}\n")
+}
+
+/// Checkstyle XML, as understood by most CI dashboards.
+fn checkstyle(formatted_paths: &[FormattedPath]) -> String {
+    let mut report = String::new();
+    report.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    report.push_str("<checkstyle version=\"4.3\">\n");
+
+    for formatted_path in formatted_paths {
+        let findings = findings(formatted_path);
+        if findings.is_empty() {
+            continue;
+        }
+
+        report.push_str(&format!(
+            "  <file name=\"{}\">\n",
+            escape(&formatted_path.path.to_string_lossy())
+        ));
+        for finding in findings {
+            report.push_str(&format!(
+                "    <error line=\"{}\"{} severity=\"{}\" message=\"{}\" source=\"alejandra.{}\"/>\n",
+                finding.lines.map_or(1, |(first, _)| first),
+                finding
+                    .columns
+                    .map_or_else(String::new, |(first, _)| format!(" column=\"{first}\"")),
+                if finding.is_error { "error" } else { "warning" },
+                escape(&finding.message),
+                finding.rule,
+            ));
+        }
+        report.push_str("  </file>\n");
+    }
+
+    report.push_str("</checkstyle>\n");
+    report
+}
+
+/// JUnit XML, with a test case per file.
+fn junit(formatted_paths: &[FormattedPath]) -> String {
+    let mut failures = 0;
+    let mut errors = 0;
+    let mut skipped = 0;
+    let mut time = 0.0;
+    let mut test_cases = String::new();
+
+    for formatted_path in formatted_paths {
+        let seconds = formatted_path.duration.as_secs_f64();
+        time += seconds;
+
+        test_cases.push_str(&format!(
+            "    <testcase name=\"{}\" classname=\"alejandra\" time=\"{seconds:.6}\"",
+            escape(&formatted_path.path.to_string_lossy())
+        ));
+
+        let findings = findings(formatted_path);
+        let (tag, kind, message) = match &formatted_path.status {
+            Status::Changed(false) if formatted_path.skipped => {
+                skipped += 1;
+                test_cases.push_str(">\n      <skipped message=\"Generated file\"/>\n    </testcase>\n");
+                continue;
+            }
+            Status::Changed(false) => {
+                test_cases.push_str("/>\n");
+                continue;
+            }
+            Status::Changed(true) => {
+                failures += 1;
+                ("failure", RULE_FORMATTING, "Requires formatting".to_string())
+            }
+            Status::Error(error) => {
+                errors += 1;
+                let kind = match error {
+                    Error::Parse(_) => RULE_SYNTAX_ERROR,
+                    _ => RULE_ERROR,
+                };
+                ("error", kind, error.to_string())
+            }
+            Status::InternalError(error) => {
+                errors += 1;
+                ("error", RULE_ERROR, format!("internal error: {error}"))
+            }
+        };
+
+        let details: Vec<String> = findings
+            .iter()
+            .map(|finding| match (finding.lines, finding.columns) {
+                (Some((line, _)), Some((column, _))) => {
+                    format!(
+                        "{}:{line}:{column}: {}",
+                        formatted_path.path.display(),
+                        finding.message
+                    )
+                }
+                (Some((line, _)), None) => {
+                    format!("{}:{line}: {}", formatted_path.path.display(), finding.message)
+                }
+                (None, _) => format!("{}: {}", formatted_path.path.display(), finding.message),
+            })
+            .collect();
+
+        test_cases.push_str(&format!(
+            ">\n      <{tag} message=\"{}\" type=\"{}\">{}</{tag}>\n    </testcase>\n",
+            escape(&message),
+            kind,
+            escape(&details.join("\n")),
+        ));
+    }
+
+    let attributes = format!(
+        "name=\"alejandra\" tests=\"{}\" failures=\"{failures}\" errors=\"{errors}\" skipped=\"{skipped}\" \
+         time=\"{time:.6}\"",
+        formatted_paths.len()
+    );
+
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites {attributes}>\n  <testsuite \
+         {attributes}>\n{test_cases}  </testsuite>\n</testsuites>\n"
+    )
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
@@ -0,0 +1,1148 @@
+use std::io::Write;
+use std::process::Command;
+use std::process::Output;
+use std::process::Stdio;
+
+use pretty_assertions::assert_eq;
+
+fn alejandra(args: &[&str], stdin: &str) -> Output {
+    alejandra_in(".", args, stdin)
+}
+
+fn alejandra_in(dir: &str, args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_alejandra"))
+        .current_dir(dir)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+/// A new, empty directory for the test called `name`.
+fn scratch_dir(name: &str) -> String {
+    let dir = format!("{}/{name}", env!("CARGO_TARGET_TMPDIR"));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Runs git in `dir`, and returns its stdout.
+fn git(dir: &str, args: &[&str]) -> Vec<u8> {
+    let output = Command::new("git")
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    output.stdout
+}
+
+/// The records that alejandra prints with `--output-format ndjson`,
+/// in order.
+fn ndjson_records(dir: &str, args: &[&str], stdin: &str) -> Vec<serde_json::Value> {
+    let output = alejandra_in(dir, &[&["--output-format", "ndjson"], args].concat(), stdin);
+
+    String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+/// The paths of the records that alejandra prints with
+/// `--output-format ndjson`, in order.
+fn ndjson_paths(dir: &str, args: &[&str], stdin: &str) -> Vec<String> {
+    ndjson_records(dir, args, stdin)
+        .iter()
+        .map(|record| record["path"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn diff() {
+    let output = alejandra(&["--quiet", "--diff", "--color", "never"], "{\n  a=1;\n}\n");
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        concat!(
+            "--- <anonymous file on stdin>\n",
+            "+++ <anonymous file on stdin>\n",
+            "@@ -1,3 +1,3 @@\n",
+            " {\n",
+            "-  a=1;\n",
+            "+  a = 1;\n",
+            " }\n",
+        )
+    );
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn ndjson() {
+    let output = alejandra(&["--output-format", "ndjson", "--check"], "{ a = ; }\n");
+    let record: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+
+    assert_eq!(record["path"], "<anonymous file on stdin>");
+    assert_eq!(record["status"], "error");
+    assert_eq!(
+        record["errors"][0]["start"],
+        serde_json::json!({ "offset": 6, "line": 1, "column": 7 })
+    );
+    assert_eq!(output.status.code(), Some(1));
+
+    // The formatted code would not be printed anywhere.
+    let output = alejandra(&["--output-format", "json"], "{a=1;}\n");
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "");
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn max_errors() {
+    let before = "{\n  a = ;\n  b = ;\n  c = ;\n}\n";
+
+    let records = ndjson_records(".", &["--check", "--max-errors", "2"], before);
+    assert_eq!(records[0]["errors"].as_array().unwrap().len(), 2);
+    assert_eq!(records[0]["omitted_errors"], 4);
+
+    let report_file = format!("{}/max_errors.xml", env!("CARGO_TARGET_TMPDIR"));
+    let output = alejandra(
+        &[
+            "--check",
+            "--max-errors",
+            "2",
+            "--report",
+            "checkstyle",
+            "--report-file",
+            &report_file,
+        ],
+        before,
+    );
+    let report = std::fs::read_to_string(&report_file).unwrap();
+    assert_eq!(report.matches("<error ").count(), 2);
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(stderr.matches("<anonymous file on stdin>: ").count(), 3);
+    assert!(stderr.contains("<anonymous file on stdin>: ... and 4 more syntax errors\n"));
+}
+
+#[test]
+fn checkstyle_report() {
+    let report_file = format!("{}/checkstyle.xml", env!("CARGO_TARGET_TMPDIR"));
+    let output = alejandra(
+        &[
+            "--quiet",
+            "--check",
+            "--report",
+            "checkstyle",
+            "--report-file",
+            &report_file,
+        ],
+        "{\n  a = 1;\n  b=2;\n}\n",
+    );
+
+    assert_eq!(
+        std::fs::read_to_string(&report_file).unwrap(),
+        concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<checkstyle version=\"4.3\">\n",
+            "  <file name=\"&lt;anonymous file on stdin&gt;\">\n",
+            "    <error line=\"3\" severity=\"warning\" message=\"Line 3 requires formatting\" \
+             source=\"alejandra.formatting\"/>\n",
+            "  </file>\n",
+            "</checkstyle>\n",
+        )
+    );
+    assert_eq!(output.status.code(), Some(2));
+}
+
+/// A directory with a file that is formatted, one that requires
+/// formatting in its third line, and one with a syntax error at 1:7.
+fn report_dir(name: &str) -> String {
+    let dir = scratch_dir(name);
+    std::fs::write(format!("{dir}/a.nix"), "{ a = 1; }\n").unwrap();
+    std::fs::write(format!("{dir}/b.nix"), "{\n  a = 1;\n  b=2;\n}\n").unwrap();
+    std::fs::write(format!("{dir}/c.nix"), "{ a = ; }\n").unwrap();
+    dir
+}
+
+#[test]
+fn sarif_report() {
+    let dir = report_dir("sarif_report");
+    let output = alejandra_in(
+        &dir,
+        &[
+            "--quiet",
+            "--check",
+            "--deterministic",
+            "always",
+            "--report",
+            "sarif",
+            "--report-file",
+            "report.sarif",
+            ".",
+        ],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(1));
+
+    let report: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(format!("{dir}/report.sarif")).unwrap()).unwrap();
+    assert_eq!(report["version"], "2.1.0");
+
+    let driver = &report["runs"][0]["tool"]["driver"];
+    assert_eq!(driver["name"], "Alejandra");
+    let rules: Vec<&str> = driver["rules"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|rule| rule["id"].as_str().unwrap())
+        .collect();
+    assert_eq!(rules, ["formatting", "syntax-error", "error"]);
+
+    let results = report["runs"][0]["results"].as_array().unwrap();
+    let summary: Vec<(&str, &str, &str)> = results
+        .iter()
+        .map(|result| {
+            (
+                result["ruleId"].as_str().unwrap(),
+                result["level"].as_str().unwrap(),
+                result["locations"][0]["physicalLocation"]["artifactLocation"]["uri"]
+                    .as_str()
+                    .unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(summary[0], ("formatting", "warning", "./b.nix"));
+    assert!(
+        summary[1..]
+            .iter()
+            .all(|result| *result == ("syntax-error", "error", "./c.nix"))
+    );
+
+    assert_eq!(
+        results[0]["locations"][0]["physicalLocation"]["region"],
+        serde_json::json!({ "startLine": 3, "endLine": 3 })
+    );
+    assert_eq!(
+        results[1]["locations"][0]["physicalLocation"]["region"],
+        serde_json::json!({ "startLine": 1, "endLine": 1, "startColumn": 7, "endColumn": 8 })
+    );
+}
+
+#[test]
+fn junit_report() {
+    let dir = report_dir("junit_report");
+    let output = alejandra_in(
+        &dir,
+        &[
+            "--quiet",
+            "--check",
+            "--deterministic",
+            "always",
+            "--report",
+            "junit",
+            "--report-file",
+            "report.xml",
+            ".",
+        ],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(1));
+
+    let report = std::fs::read_to_string(format!("{dir}/report.xml")).unwrap();
+    let lines: Vec<&str> = report.lines().collect();
+    assert_eq!(lines[0], "<?xml version=\"1.0\" encoding=\"UTF-8\"?>");
+    assert!(lines[1].starts_with(
+        "<testsuites name=\"alejandra\" tests=\"3\" failures=\"1\" errors=\"1\" skipped=\"0\" "
+    ));
+    assert!(lines[2].starts_with(
+        "  <testsuite name=\"alejandra\" tests=\"3\" failures=\"1\" errors=\"1\" skipped=\"0\" "
+    ));
+    assert!(lines[3].starts_with("    <testcase name=\"./a.nix\" classname=\"alejandra\" time=\""));
+    assert!(lines[3].ends_with("\"/>"));
+    assert!(lines[4].starts_with("    <testcase name=\"./b.nix\" "));
+    assert_eq!(
+        lines[5],
+        "      <failure message=\"Requires formatting\" type=\"formatting\">./b.nix:3: Line 3 requires \
+         formatting</failure>"
+    );
+    assert_eq!(lines[6], "    </testcase>");
+    assert!(lines[7].starts_with("    <testcase name=\"./c.nix\" "));
+    assert!(lines[8].starts_with("      <error message=\"unexpected TOKEN_SEMICOLON"));
+    assert!(lines[8].contains("type=\"syntax-error\">./c.nix:1:7: unexpected TOKEN_SEMICOLON"));
+    assert_eq!(&lines[lines.len() - 3..], [
+        "    </testcase>",
+        "  </testsuite>",
+        "</testsuites>"
+    ]);
+}
+
+#[test]
+fn staged() {
+    let repository = scratch_dir("staged");
+    git(&repository, &["init", "--quiet"]);
+    std::fs::write(format!("{repository}/staged.nix"), "{a=1;}\n").unwrap();
+    std::fs::write(format!("{repository}/unstaged.nix"), "{b=1;}\n").unwrap();
+    git(&repository, &["add", "staged.nix"]);
+    // Edits that are not staged are left alone.
+    std::fs::write(format!("{repository}/staged.nix"), "{a=2;}\n").unwrap();
+
+    let output = alejandra_in(&repository, &["--quiet", "--staged"], "");
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        String::from_utf8(git(&repository, &["show", ":staged.nix"])).unwrap(),
+        "{ a = 1; }\n"
+    );
+    assert_eq!(
+        std::fs::read_to_string(format!("{repository}/staged.nix")).unwrap(),
+        "{a=2;}\n"
+    );
+    assert_eq!(
+        std::fs::read_to_string(format!("{repository}/unstaged.nix")).unwrap(),
+        "{b=1;}\n"
+    );
+}
+
+#[test]
+fn staged_in_parallel() {
+    let repository = scratch_dir("staged_in_parallel");
+    git(&repository, &["init", "--quiet"]);
+    for index in 0..40 {
+        std::fs::write(format!("{repository}/{index}.nix"), format!("{{a={index};}}\n")).unwrap();
+    }
+    std::fs::write(format!("{repository}/latin1.nix"), b"\"\xe9\"\n").unwrap();
+    git(&repository, &["add", "."]);
+
+    let output = alejandra_in(&repository, &["--quiet", "--staged", "--threads", "16"], "");
+
+    assert_eq!(output.status.code(), Some(1));
+    for index in 0..40 {
+        assert_eq!(
+            String::from_utf8(git(&repository, &["show", &format!(":{index}.nix")])).unwrap(),
+            format!("{{ a = {index}; }}\n")
+        );
+    }
+    // Staged content that is not UTF-8 is an error, not a guess.
+    assert_eq!(git(&repository, &["show", ":latin1.nix"]), b"\"\xe9\"\n");
+}
+
+#[test]
+fn changed_since() {
+    let repository = scratch_dir("changed_since");
+    git(&repository, &["init", "--quiet"]);
+    // Names are relative to the top level of the repository even so.
+    git(&repository, &["config", "diff.relative", "true"]);
+    std::fs::create_dir_all(format!("{repository}/sub")).unwrap();
+    for file in [
+        "changed.nix",
+        "unchanged.nix",
+        "sub/changed.nix",
+        "sub/unchanged.nix",
+    ] {
+        std::fs::write(format!("{repository}/{file}"), "{a=1;}\n").unwrap();
+    }
+    git(&repository, &["add", "."]);
+    git(&repository, &[
+        "-c",
+        "user.name=test",
+        "-c",
+        "user.email=test@example.com",
+        "commit",
+        "--quiet",
+        "--message",
+        "initial",
+    ]);
+    for file in ["changed.nix", "sub/changed.nix"] {
+        std::fs::write(format!("{repository}/{file}"), "{a=2;}\n").unwrap();
+    }
+    std::fs::write(format!("{repository}/sub/added.nix"), "{a=3;}\n").unwrap();
+    git(&repository, &["add", "sub/added.nix"]);
+
+    let output = alejandra_in(
+        &format!("{repository}/sub"),
+        &["--quiet", "--changed-since", "HEAD"],
+        "",
+    );
+
+    assert_eq!(output.status.code(), Some(0));
+    for (file, expected) in [
+        ("changed.nix", "{ a = 2; }\n"),
+        ("unchanged.nix", "{a=1;}\n"),
+        ("sub/changed.nix", "{ a = 2; }\n"),
+        ("sub/unchanged.nix", "{a=1;}\n"),
+        ("sub/added.nix", "{ a = 3; }\n"),
+    ] {
+        assert_eq!(
+            std::fs::read_to_string(format!("{repository}/{file}")).unwrap(),
+            expected,
+            "{file}"
+        );
+    }
+
+    let output = alejandra_in(&repository, &["--changed-since", "no-such-revision"], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(
+        String::from_utf8(output.stderr)
+            .unwrap()
+            .contains("no-such-revision")
+    );
+}
+
+#[test]
+fn untracked() {
+    let repository = scratch_dir("untracked");
+    git(&repository, &["init", "--quiet"]);
+    std::fs::write(format!("{repository}/.gitignore"), "ignored.nix\n").unwrap();
+    for file in ["tracked.nix", "untracked.nix", "ignored.nix"] {
+        std::fs::write(format!("{repository}/{file}"), "{a=1;}\n").unwrap();
+    }
+    git(&repository, &["add", "tracked.nix"]);
+
+    let output = alejandra_in(&repository, &["--quiet", "--untracked"], "");
+
+    assert_eq!(output.status.code(), Some(0));
+    for (file, expected) in [
+        ("tracked.nix", "{a=1;}\n"),
+        ("untracked.nix", "{ a = 1; }\n"),
+        ("ignored.nix", "{a=1;}\n"),
+    ] {
+        assert_eq!(
+            std::fs::read_to_string(format!("{repository}/{file}")).unwrap(),
+            expected,
+            "{file}"
+        );
+    }
+}
+
+#[test]
+fn backup_suffix() {
+    let repository = scratch_dir("backup_suffix");
+    git(&repository, &["init", "--quiet"]);
+    for file in ["a.nix", "formatted.nix", "staged.nix"] {
+        std::fs::write(format!("{repository}/{file}"), "{a=1;}\n").unwrap();
+    }
+    std::fs::write(format!("{repository}/formatted.nix"), "{ a = 1; }\n").unwrap();
+
+    let output = alejandra_in(
+        &repository,
+        &["--quiet", "--backup-suffix", ".orig", "a.nix", "formatted.nix"],
+        "",
+    );
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        std::fs::read_to_string(format!("{repository}/a.nix")).unwrap(),
+        "{ a = 1; }\n"
+    );
+    assert_eq!(
+        std::fs::read_to_string(format!("{repository}/a.nix.orig")).unwrap(),
+        "{a=1;}\n"
+    );
+    // Files that do not change have no backup.
+    assert!(!std::path::Path::new(&format!("{repository}/formatted.nix.orig")).exists());
+
+    // The work tree is backed up too when formatting staged files.
+    git(&repository, &["add", "staged.nix"]);
+    let output = alejandra_in(
+        &repository,
+        &["--quiet", "--staged", "--backup-suffix", ".orig"],
+        "",
+    );
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        std::fs::read_to_string(format!("{repository}/staged.nix")).unwrap(),
+        "{ a = 1; }\n"
+    );
+    assert_eq!(
+        std::fs::read_to_string(format!("{repository}/staged.nix.orig")).unwrap(),
+        "{a=1;}\n"
+    );
+}
+
+#[test]
+fn ignore_files() {
+    let dir = scratch_dir("ignore_files");
+    for subdir in ["generated", "vendor", "src"] {
+        std::fs::create_dir_all(format!("{dir}/{subdir}")).unwrap();
+    }
+    for file in ["generated/a.nix", "generated/b.nix", "vendor/c.nix", "src/d.nix"] {
+        std::fs::write(format!("{dir}/{file}"), "{ }\n").unwrap();
+    }
+    std::fs::write(format!("{dir}/.alejandraignore"), "vendor/\n").unwrap();
+    std::fs::write(format!("{dir}/generated/.ignore"), "*.nix\n!b.nix\n").unwrap();
+
+    let paths = |args: &[&str]| {
+        ndjson_paths(
+            &dir,
+            &[&["--check", "--deterministic", "always", "."], args].concat(),
+            "",
+        )
+    };
+
+    assert_eq!(paths(&[]), ["./generated/b.nix", "./src/d.nix"]);
+    assert_eq!(paths(&["--no-ignore"]), [
+        "./generated/a.nix",
+        "./generated/b.nix",
+        "./src/d.nix",
+        "./vendor/c.nix",
+    ]);
+}
+
+#[test]
+fn global_ignore_files() {
+    let dir = scratch_dir("global_ignore_files");
+    let repository = format!("{dir}/repository");
+    std::fs::create_dir_all(format!("{dir}/config/git")).unwrap();
+    std::fs::create_dir_all(&repository).unwrap();
+    git(&repository, &["init", "--quiet"]);
+    for file in ["a.nix", "b.nix", "c.nix"] {
+        std::fs::write(format!("{repository}/{file}"), "{ }\n").unwrap();
+    }
+    std::fs::write(format!("{dir}/config/git/ignore"), "a.nix\n").unwrap();
+
+    let paths = || {
+        let output = Command::new(env!("CARGO_BIN_EXE_alejandra"))
+            .current_dir(&repository)
+            .args([
+                "--check",
+                "--deterministic",
+                "always",
+                "--output-format",
+                "ndjson",
+                ".",
+            ])
+            .env("HOME", &dir)
+            .env("XDG_CONFIG_HOME", format!("{dir}/config"))
+            .output()
+            .unwrap();
+
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .map(|line| {
+                let record: serde_json::Value = serde_json::from_str(line).unwrap();
+                record["path"].as_str().unwrap().to_string()
+            })
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(paths(), ["./b.nix", "./c.nix"]);
+
+    // `core.excludesFile` is read instead, when configured.
+    std::fs::write(format!("{dir}/excludes"), "b.nix\n").unwrap();
+    git(&repository, &[
+        "config",
+        "core.excludesFile",
+        &format!("{dir}/excludes"),
+    ]);
+    assert_eq!(paths(), ["./a.nix", "./c.nix"]);
+}
+
+#[test]
+fn unreadable_ignore_files() {
+    let dir = scratch_dir("unreadable_ignore_files");
+    for file in ["a.nix", "b.nix", "c.nix"] {
+        std::fs::write(format!("{dir}/{file}"), "{ }\n").unwrap();
+    }
+
+    for (content, error) in [
+        (
+            &b"a.nix\n\xe9.nix\n"[..],
+            "line 2: stream did not contain valid UTF-8",
+        ),
+        (
+            &b"a.nix\n[\n"[..],
+            "line 2: error parsing glob '[': unclosed character class; missing ']'",
+        ),
+    ] {
+        std::fs::write(format!("{dir}/.ignore"), content).unwrap();
+
+        let records = ndjson_records(&dir, &["--check", "--deterministic", "always", "."], "");
+
+        // The patterns that can be read apply even so.
+        assert_eq!(records.len(), 3);
+        assert!(records[0]["path"].as_str().unwrap().ends_with("/.ignore"));
+        assert_eq!(records[0]["errors"][0]["message"], error);
+        assert_eq!(records[1]["path"], "./b.nix");
+        assert_eq!(records[2]["path"], "./c.nix");
+    }
+}
+
+#[test]
+fn globs_match_like_git() {
+    let repository = scratch_dir("globs_match_like_git");
+    git(&repository, &["init", "--quiet"]);
+    for subdir in ["a/b/c", "b"] {
+        std::fs::create_dir_all(format!("{repository}/{subdir}")).unwrap();
+    }
+    let files = [
+        "x.nix",
+        "a/x.nix",
+        "a/b/x.nix",
+        "a/b/c/x.nix",
+        "b/y.nix",
+        "a/*.nix",
+        "a/-.nix",
+        "a/ab.nix",
+        "a/c.nix",
+    ];
+    for file in files {
+        std::fs::write(format!("{repository}/{file}"), "{ }\n").unwrap();
+    }
+    git(&repository, &["add", "."]);
+
+    for glob in [
+        // `**` at the start, at the end, in the middle, and alone.
+        "**/x.nix",
+        "a/**",
+        "**/b/**",
+        "a/**/x.nix",
+        "**",
+        // `**` that is not a whole component is like `*`.
+        "**x.nix",
+        "a**",
+        "a/b**",
+        "a/**b/x.nix",
+        // `*` and `?` do not match `/`.
+        "*.nix",
+        "a/*.nix",
+        "a/?.nix",
+        "?/x.nix",
+        // Character classes.
+        "a/[ab]*.nix",
+        "a/[!a]*.nix",
+        "a/[^a]*.nix",
+        "a/[a-b]*.nix",
+        "a/[-]*.nix",
+        "a/[*]*.nix",
+        // Escapes.
+        "a/\\*.nix",
+        "a/\\?.nix",
+        "\\x.ni?",
+    ] {
+        let mut expected: Vec<String> =
+            String::from_utf8(git(&repository, &["ls-files", &format!(":(glob){glob}")]))
+                .unwrap()
+                .lines()
+                .map(|file| format!("./{file}"))
+                .collect();
+        expected.sort();
+
+        let kept = ndjson_paths(
+            &repository,
+            &["--check", "--deterministic", "always", ".", "--exclude", glob],
+            "",
+        );
+        let mut excluded: Vec<String> = files
+            .iter()
+            .map(|file| format!("./{file}"))
+            .filter(|file| !kept.contains(file))
+            .collect();
+        excluded.sort();
+
+        assert_eq!(excluded, expected, "{glob}");
+    }
+}
+
+#[test]
+fn patterns() {
+    let dir = scratch_dir("patterns");
+    for subdir in ["a/generated", "b/generated", "c"] {
+        std::fs::create_dir_all(format!("{dir}/{subdir}")).unwrap();
+    }
+    for file in [
+        "a/x.nix",
+        "a/generated/g.nix",
+        "b/generated/h.nix",
+        "c/y.nix",
+        "z.nix",
+    ] {
+        std::fs::write(format!("{dir}/{file}"), "{ }\n").unwrap();
+    }
+
+    let paths_in = |dir: &str, args: &[&str]| {
+        ndjson_paths(
+            dir,
+            &[&["--check", "--deterministic", "always"], args].concat(),
+            "",
+        )
+    };
+    let paths = |args: &[&str]| paths_in(&dir, &[&["."], args].concat());
+
+    assert_eq!(paths(&["--exclude", "**/generated/*.nix"]), [
+        "./a/x.nix",
+        "./c/y.nix",
+        "./z.nix"
+    ]);
+    assert_eq!(paths(&["--exclude", "regex:^[ab]/", "--exclude", "z.nix"]), [
+        "./c/y.nix"
+    ]);
+    assert_eq!(paths(&["--include", "a/**", "--exclude", "a/generated"]), [
+        "./a/x.nix"
+    ]);
+
+    // Without paths, the current directory is searched instead of stdin.
+    assert_eq!(paths_in(&dir, &["--include", "c/*"]), ["./c/y.nix"]);
+
+    // Globs are relative to the current directory, not to the PATH searched.
+    assert_eq!(
+        paths_in(&dir, &["a", "--exclude", "a/generated/*.nix"]),
+        ["a/x.nix"]
+    );
+    assert_eq!(
+        paths_in(&dir, &["a/generated/g.nix", "--exclude", "a/generated/*.nix"]),
+        Vec::<String>::new()
+    );
+}
+
+#[test]
+fn config() {
+    let dir = scratch_dir("config");
+    for subdir in ["generated", "sub/generated"] {
+        std::fs::create_dir_all(format!("{dir}/{subdir}")).unwrap();
+    }
+    for file in ["a.nix", "generated/b.nix", "sub/c.nix", "sub/generated/d.nix"] {
+        std::fs::write(format!("{dir}/{file}"), "{ }\n").unwrap();
+    }
+    std::fs::write(
+        format!("{dir}/alejandra.toml"),
+        "# Shared settings\nexclude = [\n  \"generated\",\n]\nmax-errors = 3\n",
+    )
+    .unwrap();
+    std::fs::write(
+        format!("{dir}/sub/pyproject.toml"),
+        "[tool.alejandra]\nexclude = ['**/generated']\n",
+    )
+    .unwrap();
+
+    assert_eq!(
+        ndjson_paths(&dir, &["--check", "--deterministic", "always", "."], ""),
+        ["./a.nix", "./sub/c.nix"]
+    );
+
+    let output = alejandra_in(
+        &dir,
+        &["--print-config", "--max-errors", "5", "--exclude", "*.nix"],
+        "",
+    );
+    let output = String::from_utf8(output.stdout).unwrap();
+    assert!(output.contains("exclude = [\"generated\", \"*.nix\"]\n"));
+    assert!(output.contains("max-errors = 5\n"));
+}
+
+#[test]
+fn invalid_config() {
+    let dir = scratch_dir("invalid_config");
+    for subdir in ["broken", "other"] {
+        std::fs::create_dir_all(format!("{dir}/{subdir}")).unwrap();
+    }
+    for file in ["a.nix", "broken/b.nix", "other/c.nix"] {
+        std::fs::write(format!("{dir}/{file}"), "{ }\n").unwrap();
+    }
+    // Files of other tools are read only for their `[tool.alejandra]`.
+    std::fs::write(
+        format!("{dir}/pyproject.toml"),
+        concat!(
+            "[tool.poetry]\n",
+            "description = \"\"\"\nMany\nlines\"\"\"\n",
+            "version = 1.5\n",
+            "[[tool.poetry.source]]\n",
+            "name = \"mirror\"\n",
+        ),
+    )
+    .unwrap();
+    std::fs::write(format!("{dir}/other/pyproject.toml"), "[tool\n").unwrap();
+    std::fs::write(format!("{dir}/broken/alejandra.toml"), "line-width = \"wide\"\n").unwrap();
+
+    let output = alejandra_in(&dir, &["--check", "a.nix"], "");
+    assert_eq!(output.status.code(), Some(0));
+
+    let output = alejandra_in(&dir, &["--check", "--output-format", "ndjson", "."], "");
+    assert_eq!(output.status.code(), Some(1));
+    let mut records: Vec<(String, String)> = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| {
+            let record: serde_json::Value = serde_json::from_str(line).unwrap();
+            let path = record["path"].as_str().unwrap();
+            (
+                path.strip_prefix(&dir).unwrap_or(path).to_string(),
+                record["status"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect();
+    records.sort();
+    // The file that is not valid is reported once, instead of what it
+    // applies to.
+    assert_eq!(records, [
+        ("./a.nix".to_string(), "unchanged".to_string()),
+        ("./other/c.nix".to_string(), "unchanged".to_string()),
+        ("/broken/alejandra.toml".to_string(), "error".to_string()),
+    ]);
+
+    for (setting, message) in [
+        ("quiet = 3", "`quiet` must be 0, 1 or 2"),
+        ("max-errors = 0", "`max-errors` must be a positive integer"),
+    ] {
+        std::fs::write(format!("{dir}/broken/alejandra.toml"), setting).unwrap();
+
+        let output = alejandra_in(&dir, &["--check", "broken/b.nix"], "");
+        assert_eq!(output.status.code(), Some(1));
+        assert!(String::from_utf8(output.stderr).unwrap().contains(message));
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn unusual_paths() {
+    use std::os::unix::ffi::OsStrExt;
+
+    let dir = std::path::PathBuf::from(scratch_dir("unusual_paths"));
+    std::fs::write(
+        dir.join(std::ffi::OsStr::from_bytes(b"invalid-\xff.nix")),
+        "{a=1;}",
+    )
+    .unwrap();
+    std::os::unix::fs::symlink("missing.nix", dir.join("dangling.nix")).unwrap();
+
+    let output = alejandra_in(dir.to_str().unwrap(), &["--check", "invalid-\u{fffd}.nix"], "");
+    assert_eq!(output.status.code(), Some(1));
+
+    let output = alejandra_in(dir.to_str().unwrap(), &["--check", "."], "");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .contains("Requires formatting: ./invalid-\u{fffd}.nix")
+    );
+
+    let output = alejandra_in(dir.to_str().unwrap(), &["--check", "dangling.nix", "ok.nix"], "");
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("- dangling.nix: No such file or directory"));
+    assert!(stderr.contains("- ok.nix: No such file or directory"));
+}
+
+#[test]
+fn deterministic() {
+    let dir = scratch_dir("deterministic");
+    let names: Vec<String> = (0..20).map(|index| format!("{index:02}.nix")).collect();
+    for name in &names {
+        std::fs::write(format!("{dir}/{name}"), "{\n  a=1;\n}\n").unwrap();
+    }
+
+    let mut args = vec!["--check", "--threads", "4", "--deterministic", "always"];
+    args.extend(names.iter().rev().map(String::as_str));
+
+    let output = alejandra_in(&dir, &args, "");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        names
+            .iter()
+            .map(|name| format!("Requires formatting: {name}\n"))
+            .collect::<String>()
+    );
+    assert!(stderr.ends_with("Alert! 20 files require formatting.\n"));
+    assert_eq!(output.status.code(), Some(2));
+
+    assert_eq!(ndjson_paths(&dir, &args, ""), names);
+}
+
+#[test]
+fn verify_bom_and_crlf() {
+    for verify in ["--verify", "--verify-idempotent"] {
+        let output = alejandra(&["--quiet", verify], "\u{feff}{ a=1;\r\n}\r\n");
+
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            "\u{feff}{\r\n  a = 1;\r\n}\r\n"
+        );
+        assert_eq!(output.status.code(), Some(0));
+    }
+}
+
+#[test]
+fn verify_failure() {
+    let dir = scratch_dir("verify_failure");
+    // Trailing whitespace is removed from indented strings,
+    // which changes their value.
+    let before = "{\n  a = ''\n    x\t\n  '';\n}\n";
+    std::fs::write(format!("{dir}/a.nix"), before).unwrap();
+
+    let output = alejandra_in(&dir, &["--quiet", "--verify", "a.nix"], "");
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr).unwrap().contains(
+        "a.nix: verification failed: TOKEN_STRING_CONTENT \"x\\t\\n\" at 2:7 of the input became \
+         TOKEN_STRING_CONTENT \"x\\n\" at 2:7 of the output\n"
+    ));
+    assert_eq!(std::fs::read_to_string(format!("{dir}/a.nix")).unwrap(), before);
+}
+
+#[test]
+fn verify_idempotent_failure() {
+    // A comment after `(let` moves to a line of its own,
+    // where it is formatted differently.
+    let before = "(let # 1\n  a = 1;\nin\n  a)\n";
+
+    let output = alejandra(&["--quiet", "--verify-idempotent"], before);
+
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), before);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr).unwrap().contains(concat!(
+        "<anonymous file on stdin>: verification failed: the second pass changes line 2:\n",
+        "      1 | (\n",
+        "-     2 | let\n",
+        "+     2 | \n",
+    )));
+}
+
+#[test]
+fn cursor_offset() {
+    for (before, after) in [
+        ("{a=1;\nb=2;}\n", "{\n  a = 1;\n  b = 2;\n}\n"),
+        (
+            "\u{feff}{a=1;\r\nb=2;}\r\n",
+            "\u{feff}{\r\n  a = 1;\r\n  b = 2;\r\n}\r\n",
+        ),
+    ] {
+        // The cursor is on the `2`.
+        let cursor = before.find('2').unwrap();
+        let output = alejandra(&["--quiet", "--cursor-offset", &cursor.to_string()], before);
+
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            format!("{{ \"Cursor\": {} }}\n{after}", after.find('2').unwrap())
+        );
+        assert_eq!(output.status.code(), Some(0));
+    }
+}
+
+#[test]
+fn stdin() {
+    let output = alejandra(&["--quiet", "--check"], "{a=1;}\n");
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "");
+    assert_eq!(output.status.code(), Some(2));
+
+    let output = alejandra(&["--quiet", "--check"], "{ a = 1; }\n");
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "");
+    assert_eq!(output.status.code(), Some(0));
+
+    let dir = scratch_dir("stdin");
+    std::fs::write(format!("{dir}/alejandra.toml"), "exclude = [\"generated\"]\n").unwrap();
+
+    let output = alejandra_in(
+        &dir,
+        &["--quiet", "--stdin-filepath", "generated/a.nix"],
+        "{a=1;}\n",
+    );
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "{a=1;}\n");
+    assert_eq!(output.status.code(), Some(0));
+
+    let output = alejandra_in(
+        &dir,
+        &[
+            "--quiet",
+            "--stdin-filepath",
+            "src/a.nix",
+            "--output-format",
+            "ndjson",
+            "--check",
+        ],
+        "{a=1;}\n",
+    );
+    let record: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(record["path"], "src/a.nix");
+    assert_eq!(record["status"], "changed");
+    assert_eq!(output.status.code(), Some(2));
+
+    let output = alejandra_in(&dir, &["--stdin-filepath", "a.nix", "."], "");
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn range() {
+    let before = "# header\nlet\n  a   =   1;\n  b   =  [1   2];\nin   a\n";
+    let formatted = |args: &[&str]| {
+        let output = alejandra(&[&["--quiet"], args].concat(), before);
+        assert_eq!(output.status.code(), Some(0));
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    // Inside one node.
+    assert_eq!(
+        formatted(&["--lines", "3-3"]),
+        "# header\nlet\n  a = 1;\n  b   =  [1   2];\nin   a\n"
+    );
+    let start = before.find("b   =").unwrap();
+    assert_eq!(
+        formatted(&["--range", &format!("{start}:{}", start + 1)]),
+        "# header\nlet\n  a   =   1;\n  b = [1 2];\nin   a\n"
+    );
+
+    // Spanning nodes.
+    assert_eq!(
+        formatted(&["--lines", "3-4"]),
+        "# header\nlet\n  a = 1;\n  b = [1 2];\nin   a\n"
+    );
+
+    // Touching no code.
+    assert_eq!(formatted(&["--lines", "1-1"]), before);
+    assert_eq!(formatted(&["--range", "100:200"]), before);
+}
+
+#[test]
+fn files_from() {
+    let dir = scratch_dir("files_from");
+    for subdir in ["generated", "vendor", "src"] {
+        std::fs::create_dir_all(format!("{dir}/{subdir}")).unwrap();
+    }
+    for file in ["generated/a.nix", "vendor/b.nix", "src/c.nix", "src/d.txt"] {
+        std::fs::write(format!("{dir}/{file}"), "{ }\n").unwrap();
+    }
+    std::fs::write(format!("{dir}/.alejandraignore"), "vendor/\n").unwrap();
+    std::fs::write(
+        format!("{dir}/list.txt"),
+        "generated/a.nix\nvendor/b.nix\nsrc/c.nix\nsrc/d.txt\nsrc\n",
+    )
+    .unwrap();
+
+    let paths = |args: &[&str], stdin: &str| {
+        ndjson_paths(
+            &dir,
+            &[&["--check", "--deterministic", "always"], args].concat(),
+            stdin,
+        )
+    };
+
+    assert_eq!(
+        paths(&["--files-from", "list.txt", "--exclude", "generated"], ""),
+        ["src/c.nix"]
+    );
+    assert_eq!(
+        paths(
+            &["--files-from", "-", "-0", "--no-ignore"],
+            "vendor/b.nix\0src/c.nix\0"
+        ),
+        ["src/c.nix", "vendor/b.nix"]
+    );
+}
+
+#[test]
+fn format_options() {
+    let output = alejandra(
+        &["--quiet", "--indent-width", "4", "--line-ending", "crlf"],
+        "{\na=1;\n}\n",
+    );
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "{\r\n    a = 1;\r\n}\r\n"
+    );
+
+    let dir = scratch_dir("format_options");
+    std::fs::write(
+        format!("{dir}/alejandra.toml"),
+        "indent-style = \"tabs\"\nfinal-newline = false\n",
+    )
+    .unwrap();
+
+    let output = alejandra_in(&dir, &["--quiet", "-"], "{\na=1;\n}\n");
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "{\n\ta = 1;\n}");
+
+    let output = alejandra_in(&dir, &["--print-config", "--indent-width", "4"], "");
+    let output = String::from_utf8(output.stdout).unwrap();
+    assert!(output.contains("indent-width = 4\n"));
+    assert!(output.contains("indent-style = \"tabs\"\n"));
+    assert!(output.contains("final-newline = false\n"));
+
+    // Each file is formatted with the settings of its nearest configuration.
+    std::fs::create_dir_all(format!("{dir}/sub")).unwrap();
+    std::fs::write(format!("{dir}/sub/alejandra.toml"), "indent-width = 4\n").unwrap();
+    std::fs::write(format!("{dir}/a.nix"), "{\na=1;\n}\n").unwrap();
+    std::fs::write(format!("{dir}/sub/b.nix"), "{\nb=1;\n}\n").unwrap();
+
+    let output = alejandra_in(&dir, &["--quiet", "."], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        std::fs::read_to_string(format!("{dir}/a.nix")).unwrap(),
+        "{\n\ta = 1;\n}"
+    );
+    assert_eq!(
+        std::fs::read_to_string(format!("{dir}/sub/b.nix")).unwrap(),
+        "{\n    b = 1;\n}\n"
+    );
+}
+
+#[test]
+fn generated() {
+    let dir = scratch_dir("generated");
+    std::fs::write(format!("{dir}/a.nix"), "{a=1;}\n").unwrap();
+    std::fs::write(
+        format!("{dir}/b.nix"),
+        "# Generated by node2nix. DO NOT EDIT!\n{a=1;}\n",
+    )
+    .unwrap();
+    std::fs::write(format!("{dir}/c.nix"), "/* made by hand */\n{a=1;}\n").unwrap();
+
+    let statuses = |args: &[&str]| -> Vec<String> {
+        ndjson_records(&dir, args, "")
+            .iter()
+            .map(|record| {
+                format!(
+                    "{} {}",
+                    record["path"].as_str().unwrap(),
+                    record["status"].as_str().unwrap()
+                )
+            })
+            .collect()
+    };
+
+    let args = ["--check", "--deterministic", "always", "."];
+    assert_eq!(statuses(&args), [
+        "./a.nix changed",
+        "./b.nix skipped",
+        "./c.nix changed"
+    ]);
+
+    let args = [&args[..], &["--format-generated"]].concat();
+    assert_eq!(statuses(&args), [
+        "./a.nix changed",
+        "./b.nix changed",
+        "./c.nix changed"
+    ]);
+
+    std::fs::write(
+        format!("{dir}/alejandra.toml"),
+        "generated-markers = [\"by hand\"]\n",
+    )
+    .unwrap();
+    let args = ["--check", "--deterministic", "always", "."];
+    assert_eq!(statuses(&args), [
+        "./a.nix changed",
+        "./b.nix changed",
+        "./c.nix skipped"
+    ]);
+
+    let output = alejandra_in(&dir, &["--quiet"], "# made by hand\n{a=1;}\n");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "# made by hand\n{a=1;}\n"
+    );
+}
@@ -0,0 +1,4 @@
+mod position;
+mod protocol;
+pub mod server;
+mod uri;
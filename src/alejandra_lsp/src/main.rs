@@ -0,0 +1,3 @@
+fn main() -> std::io::Result<()> {
+    alejandra_lsp::server::main()
+}
@@ -0,0 +1,40 @@
+/// Translates a byte `offset` of `text` to an LSP position,
+/// whose character is counted in UTF-16 code units.
+pub(crate) fn to_lsp(text: &str, offset: usize) -> serde_json::Value {
+    let offset = offset.min(text.len());
+    let line_start = text[..offset].rfind('\n').map_or(0, |index| index + 1);
+
+    let line = text[..line_start].matches('\n').count();
+    let character: usize = text[line_start..offset].chars().map(char::len_utf16).sum();
+
+    serde_json::json!({ "line": line, "character": character })
+}
+
+/// Translates an LSP position to a byte offset of `text`,
+/// clamping it to the end of the line or the end of the text.
+pub(crate) fn from_lsp(text: &str, position: &serde_json::Value) -> usize {
+    let line = position["line"].as_u64().unwrap_or(0) as usize;
+    let character = position["character"].as_u64().unwrap_or(0) as usize;
+
+    let line_start = match line {
+        0 => 0,
+        line => match text.match_indices('\n').nth(line - 1) {
+            Some((index, _)) => index + 1,
+            None => return text.len(),
+        },
+    };
+
+    let mut units = 0;
+    for (index, char) in text[line_start..].char_indices() {
+        if char == '\n' || units >= character {
+            return line_start + index;
+        }
+        units += char.len_utf16();
+    }
+
+    text.len()
+}
+
+pub(crate) fn range_to_lsp(text: &str, start: usize, end: usize) -> serde_json::Value {
+    serde_json::json!({ "start": to_lsp(text, start), "end": to_lsp(text, end) })
+}
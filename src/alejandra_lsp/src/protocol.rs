@@ -0,0 +1,52 @@
+use std::io::BufRead;
+use std::io::Write;
+
+/// Reads a message framed with a `Content-Length` header,
+/// or why it is not valid,
+/// returns `None` when the input ends.
+pub(crate) fn read_message(
+    reader: &mut impl BufRead,
+) -> std::io::Result<Option<Result<serde_json::Value, String>>> {
+    let mut content_length = None;
+
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                content_length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+
+    let content_length = match content_length {
+        Some(content_length) => content_length,
+        None => return Ok(Some(Err("missing Content-Length header".to_string()))),
+    };
+
+    let mut content = vec![0; content_length];
+    match reader.read_exact(&mut content) {
+        Ok(()) => {}
+        Err(error) if error.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(error) => return Err(error),
+    }
+
+    Ok(Some(
+        serde_json::from_slice(&content).map_err(|error| format!("invalid JSON: {error}")),
+    ))
+}
+
+pub(crate) fn write_message(writer: &mut impl Write, message: &serde_json::Value) -> std::io::Result<()> {
+    let content = message.to_string();
+
+    write!(writer, "Content-Length: {}\r\n\r\n{content}", content.len())?;
+    writer.flush()
+}
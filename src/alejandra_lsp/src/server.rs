@@ -0,0 +1,246 @@
+use std::collections::HashMap;
+use std::io::Write;
+
+use alejandra::format::Status;
+use alejandra::options::FormatOptions;
+use alejandra::options::IndentStyle;
+use serde_json::json;
+use serde_json::Value;
+
+use crate::position::from_lsp;
+use crate::position::range_to_lsp;
+use crate::protocol::read_message;
+use crate::protocol::write_message;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_PARAMS: i64 = -32602;
+const METHOD_NOT_FOUND: i64 = -32601;
+const REQUEST_FAILED: i64 = -32803;
+
+/// Full text of the document is sent on every change.
+const TEXT_DOCUMENT_SYNC_FULL: u8 = 1;
+
+struct Server<W: Write> {
+    /// Text of the open documents, by URI.
+    documents: HashMap<String, String>,
+    shutdown:  bool,
+    writer:    W,
+}
+
+/// Speaks the Language Server Protocol over stdin and stdout.
+pub fn main() -> std::io::Result<()> {
+    let mut reader = std::io::stdin().lock();
+    let mut server = Server {
+        documents: HashMap::new(),
+        shutdown:  false,
+        writer:    std::io::stdout().lock(),
+    };
+
+    while let Some(message) = read_message(&mut reader)? {
+        let message = match message {
+            Ok(message) => message,
+            // Its id is unknown, so the response has none.
+            Err(error) => {
+                let response = json!({
+                    "jsonrpc": "2.0",
+                    "id": null,
+                    "error": { "code": PARSE_ERROR, "message": format!("Parse error: {error}") },
+                });
+                write_message(&mut server.writer, &response)?;
+                continue;
+            }
+        };
+
+        if message["method"] == "exit" {
+            std::process::exit(if server.shutdown { 0 } else { 1 });
+        }
+
+        server.handle(message)?;
+    }
+
+    Ok(())
+}
+
+impl<W: Write> Server<W> {
+    fn handle(&mut self, message: Value) -> std::io::Result<()> {
+        let method = match message["method"].as_str() {
+            Some(method) => method,
+            // A response to a request of ours, we send none.
+            None => return Ok(()),
+        };
+        let params = &message["params"];
+
+        let id = match message.get("id") {
+            Some(id) => id,
+            None => return self.notification(method, params),
+        };
+
+        let result = match method {
+            "initialize" => Ok(json!({
+                "capabilities": {
+                    "documentFormattingProvider": true,
+                    "documentRangeFormattingProvider": true,
+                    "textDocumentSync": TEXT_DOCUMENT_SYNC_FULL,
+                },
+                "serverInfo": {
+                    "name": "Alejandra",
+                    "version": alejandra::version::VERSION,
+                },
+            })),
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Value::Null)
+            }
+            "textDocument/formatting" => self.formatting(params),
+            "textDocument/rangeFormatting" => self.range_formatting(params),
+            method => Err((METHOD_NOT_FOUND, format!("Unknown method: {method}"))),
+        };
+
+        let response = match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": code, "message": message },
+            }),
+        };
+
+        write_message(&mut self.writer, &response)
+    }
+
+    fn notification(&mut self, method: &str, params: &Value) -> std::io::Result<()> {
+        let uri = match params["textDocument"]["uri"].as_str() {
+            Some(uri) => uri.to_string(),
+            None => return Ok(()),
+        };
+
+        match method {
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.documents.insert(uri.clone(), text.to_string());
+                self.publish_diagnostics(&uri)
+            }
+            "textDocument/didChange" => {
+                let changes = params["contentChanges"].as_array();
+                if let Some(text) = changes.and_then(|changes| changes.last()?["text"].as_str()) {
+                    self.documents.insert(uri.clone(), text.to_string());
+                }
+                self.publish_diagnostics(&uri)
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                self.publish_diagnostics(&uri)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn document<'a>(&'a self, params: &'a Value) -> Result<(&'a str, &'a String), (i64, String)> {
+        let uri = params["textDocument"]["uri"]
+            .as_str()
+            .ok_or((INVALID_PARAMS, "Missing textDocument.uri".to_string()))?;
+
+        match self.documents.get(uri) {
+            Some(text) => Ok((uri, text)),
+            None => Err((INVALID_PARAMS, format!("Document is not open: {uri}"))),
+        }
+    }
+
+    fn formatting(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let (uri, text) = self.document(params)?;
+        let options = format_options(uri, params)?;
+
+        let (status, after) = alejandra::format::in_memory_with(text.clone(), &options);
+
+        Ok(match status {
+            Status::Changed(true) => json!([{
+                "range": range_to_lsp(text, 0, text.len()),
+                "newText": after,
+            }]),
+            _ => json!([]),
+        })
+    }
+
+    fn range_formatting(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let (uri, text) = self.document(params)?;
+        let options = format_options(uri, params)?;
+
+        let start = from_lsp(text, &params["range"]["start"]);
+        let end = from_lsp(text, &params["range"]["end"]).max(start);
+        let range = rnix::TextRange::new((start as u32).into(), (end as u32).into());
+
+        let (status, after, edited) = alejandra::format::in_memory_range(
+            uri.to_string(),
+            text.clone(),
+            range,
+            &options,
+        );
+
+        Ok(match status {
+            Status::Changed(true) => {
+                let new_start: usize = edited.start().into();
+                let new_end: usize = edited.end().into();
+                // Everything after the edited range was left untouched.
+                let old_end = new_end + text.len() - after.len();
+
+                json!([{
+                    "range": range_to_lsp(text, new_start, old_end),
+                    "newText": &after[new_start..new_end],
+                }])
+            }
+            _ => json!([]),
+        })
+    }
+
+    fn publish_diagnostics(&mut self, uri: &str) -> std::io::Result<()> {
+        let diagnostics: Vec<Value> = match self.documents.get(uri) {
+            Some(text) => match alejandra::format::check_syntax(text) {
+                Err(alejandra::error::Error::Parse(errors)) => errors
+                    .iter()
+                    .map(|error| {
+                        json!({
+                            "range": range_to_lsp(text, error.range.start().into(), error.range.end().into()),
+                            "severity": 1,
+                            "source": "alejandra",
+                            "message": error.message,
+                        })
+                    })
+                    .collect(),
+                _ => Vec::new(),
+            },
+            None => Vec::new(),
+        };
+
+        let notification = json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": { "uri": uri, "diagnostics": diagnostics },
+        });
+
+        write_message(&mut self.writer, &notification)
+    }
+}
+
+/// The formatting options of the request,
+/// overridden by the configuration file of the project, if any.
+fn format_options(uri: &str, params: &Value) -> Result<FormatOptions, (i64, String)> {
+    let mut options = FormatOptions::default();
+
+    let tab_size = params["options"]["tabSize"].as_u64().filter(|tab_size| *tab_size > 0);
+    if let Some(tab_size) = tab_size {
+        options = options.with_indent_width(tab_size as usize);
+    }
+    if let Some(insert_spaces) = params["options"]["insertSpaces"].as_bool() {
+        options = options.with_indent_style(if insert_spaces {
+            IndentStyle::Spaces
+        } else {
+            IndentStyle::Tabs
+        });
+    }
+
+    match crate::uri::to_path(uri) {
+        Some(path) => alejandra_cli::config::format_options_for(&path, options)
+            .map_err(|error| (REQUEST_FAILED, error)),
+        None => Ok(options),
+    }
+}
@@ -0,0 +1,309 @@
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Read;
+use std::io::Write;
+use std::process::Child;
+use std::process::ChildStdin;
+use std::process::ChildStdout;
+use std::process::Command;
+use std::process::Stdio;
+
+use serde_json::json;
+use serde_json::Value;
+
+fn send(stdin: &mut impl Write, message: Value) {
+    let content = message.to_string();
+    write!(stdin, "Content-Length: {}\r\n\r\n{content}", content.len()).unwrap();
+    stdin.flush().unwrap();
+}
+
+fn receive(stdout: &mut BufReader<ChildStdout>) -> Value {
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        stdout.read_line(&mut header).unwrap();
+        match header.trim_end().split_once(": ") {
+            Some(("Content-Length", value)) => content_length = value.parse().unwrap(),
+            _ => break,
+        }
+    }
+
+    let mut content = vec![0; content_length];
+    stdout.read_exact(&mut content).unwrap();
+    serde_json::from_slice(&content).unwrap()
+}
+
+/// Starts a server, with pipes to its stdin and stdout.
+fn start() -> (Child, ChildStdin, BufReader<ChildStdout>) {
+    let mut server = Command::new(env!("CARGO_BIN_EXE_alejandra-lsp"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let stdin = server.stdin.take().unwrap();
+    let stdout = BufReader::new(server.stdout.take().unwrap());
+
+    (server, stdin, stdout)
+}
+
+/// Opens the document at `uri`, and returns the diagnostics published for it.
+fn open(stdin: &mut ChildStdin, stdout: &mut BufReader<ChildStdout>, uri: &str, text: &str) -> Value {
+    send(
+        stdin,
+        json!({"jsonrpc": "2.0", "method": "textDocument/didOpen", "params": {
+            "textDocument": {"uri": uri, "languageId": "nix", "version": 1, "text": text}
+        }}),
+    );
+    receive(stdout)
+}
+
+#[test]
+fn session() {
+    let (mut server, mut stdin, mut stdout) = start();
+
+    let uri = "file:///test.nix";
+
+    send(
+        &mut stdin,
+        json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}}),
+    );
+    let response = receive(&mut stdout);
+    assert_eq!(
+        response["result"]["capabilities"]["documentFormattingProvider"],
+        true
+    );
+
+    // Syntax errors are published as diagnostics
+    let diagnostics = open(&mut stdin, &mut stdout, uri, "{\n  a = ;\n}\n");
+    assert_eq!(diagnostics["method"], "textDocument/publishDiagnostics");
+    assert_eq!(
+        diagnostics["params"]["diagnostics"][0]["range"]["start"],
+        json!({"line": 1, "character": 6})
+    );
+
+    // Fixing them clears the diagnostics
+    send(
+        &mut stdin,
+        json!({"jsonrpc": "2.0", "method": "textDocument/didChange", "params": {
+            "textDocument": {"uri": uri, "version": 2},
+            "contentChanges": [{"text": "{\n    a   = 1;\n    b   = 2;\n}\n"}]
+        }}),
+    );
+    let diagnostics = receive(&mut stdout);
+    assert_eq!(diagnostics["params"]["diagnostics"], json!([]));
+
+    send(
+        &mut stdin,
+        json!({"jsonrpc": "2.0", "id": 2, "method": "textDocument/formatting", "params": {
+            "textDocument": {"uri": uri}, "options": {"tabSize": 2, "insertSpaces": true}
+        }}),
+    );
+    let response = receive(&mut stdout);
+    assert_eq!(response["result"][0]["newText"], "{\n  a = 1;\n  b = 2;\n}\n");
+
+    send(
+        &mut stdin,
+        json!({"jsonrpc": "2.0", "id": 3, "method": "textDocument/rangeFormatting", "params": {
+            "textDocument": {"uri": uri},
+            "range": {"start": {"line": 2, "character": 4}, "end": {"line": 2, "character": 4}},
+            "options": {"tabSize": 2, "insertSpaces": true}
+        }}),
+    );
+    let response = receive(&mut stdout);
+    assert_eq!(
+        response["result"],
+        json!([{
+            "range": {"start": {"line": 2, "character": 4}, "end": {"line": 2, "character": 12}},
+            "newText": "b = 2;",
+        }])
+    );
+
+    send(
+        &mut stdin,
+        json!({"jsonrpc": "2.0", "id": 4, "method": "shutdown"}),
+    );
+    assert_eq!(receive(&mut stdout)["id"], 4);
+    send(&mut stdin, json!({"jsonrpc": "2.0", "method": "exit"}));
+
+    assert!(server.wait().unwrap().success());
+}
+
+#[test]
+fn formatting_options() {
+    let dir = format!("{}/formatting_options", env!("CARGO_TARGET_TMPDIR"));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(format!("{dir}/project")).unwrap();
+    std::fs::write(
+        format!("{dir}/project/alejandra.toml"),
+        "indent-style = \"spaces\"\nindent-width = 4\n",
+    )
+    .unwrap();
+
+    let (mut server, mut stdin, mut stdout) = start();
+
+    // The options of the editor apply, unless the project gives others.
+    for (id, path, expected) in [
+        (1, "a.nix", "{\n\ta = 1;\n}\n"),
+        (2, "project/a%20b.nix", "{\n    a = 1;\n}\n"),
+        (3, "project/sub/c.nix", "{\n    a = 1;\n}\n"),
+    ] {
+        let uri = format!("file://{dir}/{path}");
+        open(&mut stdin, &mut stdout, &uri, "{\na=1;\n}\n");
+
+        send(
+            &mut stdin,
+            json!({"jsonrpc": "2.0", "id": id, "method": "textDocument/formatting", "params": {
+                "textDocument": {"uri": uri}, "options": {"tabSize": 8, "insertSpaces": false}
+            }}),
+        );
+        assert_eq!(receive(&mut stdout)["result"][0]["newText"], expected);
+    }
+
+    std::fs::write(
+        format!("{dir}/project/alejandra.toml"),
+        "indent-style = \"spaces\"\n",
+    )
+    .unwrap();
+    let uri = format!("file://{dir}/project/a%20b.nix");
+    send(
+        &mut stdin,
+        json!({"jsonrpc": "2.0", "id": 4, "method": "textDocument/formatting", "params": {
+            "textDocument": {"uri": uri}, "options": {"tabSize": 3, "insertSpaces": false}
+        }}),
+    );
+    assert_eq!(receive(&mut stdout)["result"][0]["newText"], "{\n   a = 1;\n}\n");
+
+    std::fs::write(format!("{dir}/project/alejandra.toml"), "indent-width = 0\n").unwrap();
+    send(
+        &mut stdin,
+        json!({"jsonrpc": "2.0", "id": 5, "method": "textDocument/formatting", "params": {
+            "textDocument": {"uri": uri}, "options": {"tabSize": 2, "insertSpaces": true}
+        }}),
+    );
+    let response = receive(&mut stdout);
+    assert_eq!(response["error"]["code"], -32803);
+    assert!(
+        response["error"]["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("Invalid configuration file at ")
+    );
+
+    drop(stdin);
+    assert!(server.wait().unwrap().success());
+}
+
+#[test]
+fn malformed_messages() {
+    let (mut server, mut stdin, mut stdout) = start();
+
+    for malformed in [
+        "Content-Length: 5\r\n\r\n{oops",
+        "Content-Type: application/vscode-jsonrpc\r\n\r\n",
+    ] {
+        write!(stdin, "{malformed}").unwrap();
+        stdin.flush().unwrap();
+
+        let response = receive(&mut stdout);
+        assert_eq!(response["id"], Value::Null);
+        assert_eq!(response["error"]["code"], -32700);
+    }
+
+    // The server keeps going.
+    send(
+        &mut stdin,
+        json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}}),
+    );
+    assert_eq!(receive(&mut stdout)["id"], 1);
+
+    drop(stdin);
+    assert!(server.wait().unwrap().success());
+}
+
+#[test]
+fn diagnostics() {
+    let (mut server, mut stdin, mut stdout) = start();
+
+    // Unformatted but valid code has no diagnostics.
+    let diagnostics = open(&mut stdin, &mut stdout, "file:///valid.nix", "{\na=1;\n}\n");
+    assert_eq!(diagnostics["params"]["uri"], "file:///valid.nix");
+    assert_eq!(diagnostics["params"]["diagnostics"], json!([]));
+
+    // Every syntax error is reported, in order, at its position in the editor:
+    // lines are counted after a CRLF, and characters in UTF-16 code units.
+    let diagnostics = open(
+        &mut stdin,
+        &mut stdout,
+        "file:///invalid.nix",
+        "{\r\n  \"é\" = ;\r\n  b = ;\r\n}\r\n",
+    );
+    let diagnostics = diagnostics["params"]["diagnostics"].as_array().unwrap();
+    assert_eq!(diagnostics.len(), 2);
+    assert_eq!(
+        diagnostics[0]["range"]["start"],
+        json!({"line": 1, "character": 8})
+    );
+    assert_eq!(
+        diagnostics[1]["range"]["start"],
+        json!({"line": 2, "character": 4})
+    );
+    for diagnostic in diagnostics {
+        assert_eq!(diagnostic["severity"], 1);
+        assert_eq!(diagnostic["source"], "alejandra");
+        assert!(!diagnostic["message"].as_str().unwrap().is_empty());
+    }
+
+    drop(stdin);
+    assert!(server.wait().unwrap().success());
+}
+
+#[test]
+fn range_formatting() {
+    let (mut server, mut stdin, mut stdout) = start();
+
+    let uri = "file:///range.nix";
+    open(&mut stdin, &mut stdout, uri, "{\n\ta = 1;\n  b   = [ 2 ];\n}\n");
+
+    for (id, line, character, expected) in [
+        // Only the binding at the range is formatted, with the options of the editor.
+        (
+            1,
+            2,
+            2,
+            json!([{
+                "range": {"start": {"line": 2, "character": 2}, "end": {"line": 2, "character": 14}},
+                "newText": "b = [2];",
+            }]),
+        ),
+        // A range in code that is already formatted gives no edit.
+        (2, 1, 1, json!([])),
+    ] {
+        send(
+            &mut stdin,
+            json!({"jsonrpc": "2.0", "id": id, "method": "textDocument/rangeFormatting", "params": {
+                "textDocument": {"uri": uri},
+                "range": {
+                    "start": {"line": line, "character": character},
+                    "end": {"line": line, "character": character}
+                },
+                "options": {"tabSize": 4, "insertSpaces": false}
+            }}),
+        );
+        assert_eq!(receive(&mut stdout)["result"], expected);
+    }
+
+    // Invalid code is left alone.
+    open(&mut stdin, &mut stdout, uri, "{\n  a   = ;\n}\n");
+    send(
+        &mut stdin,
+        json!({"jsonrpc": "2.0", "id": 3, "method": "textDocument/rangeFormatting", "params": {
+            "textDocument": {"uri": uri},
+            "range": {"start": {"line": 1, "character": 2}, "end": {"line": 1, "character": 2}},
+            "options": {"tabSize": 2, "insertSpaces": true}
+        }}),
+    );
+    assert_eq!(receive(&mut stdout)["result"], json!([]));
+
+    drop(stdin);
+    assert!(server.wait().unwrap().success());
+}